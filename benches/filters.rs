@@ -0,0 +1,79 @@
+//! Run with `cargo bench --bench filters --features parallel` to compare one
+//! thread against the whole pool; without the feature both measure the
+//! serial loops.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use rust_image_processing::image::{Rgb, RgbImage};
+use rust_image_processing::*;
+
+/// Deterministic pseudo random image, so runs compare the same inputs.
+fn image(size: u32) -> RgbImage {
+    let mut state: u32 = 1;
+    RgbImage::from_fn(size, size, |_, _| {
+        state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        let [r, g, b, _] = state.to_be_bytes();
+        Rgb([r, g, b])
+    })
+}
+
+type Run = fn(RgbImage) -> RgbImage;
+
+const FILTERS: [(&str, Run); 7] = [
+    ("bright", |mut img| {
+        bright(&mut img, 1.3);
+        img
+    }),
+    ("colorize", |mut img| {
+        colorize(&mut img, Rgb([255, 128, 0]));
+        img
+    }),
+    ("add", |mut img| {
+        add(&mut img, Rgb([16, 32, 48]));
+        img
+    }),
+    ("pinkize", |mut img| {
+        pinkize(&mut img);
+        img
+    }),
+    ("nval", |mut img| {
+        to_n_val_channels(&mut img, 3);
+        img
+    }),
+    ("editcolor", |mut img| {
+        edit_color(
+            &mut img,
+            vec![Rgb([0, 0, 0]), Rgb([255, 0, 0])],
+            ColorTolerance::PerChannel(64.0),
+        );
+        img
+    }),
+    ("upscale", |img| upscale(img, 2).unwrap()),
+];
+
+fn filters(c: &mut Criterion) {
+    let img = image(2048);
+    let pools: [(&str, ThreadPool); 2] = [
+        (
+            "1_thread",
+            ThreadPoolBuilder::new().num_threads(1).build().unwrap(),
+        ),
+        ("all_threads", ThreadPoolBuilder::new().build().unwrap()),
+    ];
+    for (name, run) in FILTERS {
+        let expected = pools[0].1.install(|| run(img.clone()));
+        assert!(pools[1].1.install(|| run(img.clone())) == expected);
+
+        let mut group = c.benchmark_group(format!("filters/{}", name));
+        group.sample_size(10);
+        for (threads, pool) in &pools {
+            group.bench_function(*threads, |b| {
+                b.iter(|| pool.install(|| black_box(run(black_box(img.clone())))))
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, filters);
+criterion_main!(benches);
@@ -0,0 +1,53 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use rust_image_processing::nearest::{nearest_index_brute, PaletteMatcher};
+use rust_image_processing::ColorMetric;
+
+/// Deterministic pseudo random colors, so runs compare the same inputs.
+fn colors(n: usize, mut state: u32) -> Vec<[u8; 3]> {
+    (0..n)
+        .map(|_| {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            let [r, g, b, _] = state.to_be_bytes();
+            [r, g, b]
+        })
+        .collect()
+}
+
+fn nearest(c: &mut Criterion) {
+    let pixels = colors(4096, 1);
+    for metric in [
+        ColorMetric::Euclidean,
+        ColorMetric::Cie76,
+        ColorMetric::Oklab,
+    ] {
+        let mut group = c.benchmark_group(format!("nearest/{}", metric.name()));
+        for size in [16, 64, 256] {
+            let palette = colors(size, size as u32);
+            let matcher = PaletteMatcher::new(palette.clone(), metric).unwrap();
+            for &pixel in &pixels {
+                assert_eq!(
+                    matcher.nearest_index(pixel),
+                    nearest_index_brute(&palette, pixel, metric)
+                );
+            }
+            group.bench_with_input(BenchmarkId::new("brute", size), &size, |b, _| {
+                b.iter(|| {
+                    for &pixel in &pixels {
+                        black_box(nearest_index_brute(&palette, black_box(pixel), metric));
+                    }
+                })
+            });
+            group.bench_with_input(BenchmarkId::new("kd_tree", size), &size, |b, _| {
+                b.iter(|| {
+                    for &pixel in &pixels {
+                        black_box(matcher.nearest_index(black_box(pixel)));
+                    }
+                })
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, nearest);
+criterion_main!(benches);
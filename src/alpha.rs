@@ -0,0 +1,141 @@
+use crate::depth::{from_u8_scale, Channel};
+use crate::threshold::ThresholdMap;
+use image::{ImageBuffer, Pixel, Rgb, Rgba};
+
+/// What happens to transparency when an RGBA image goes through the RGB
+/// processing functions.
+///
+/// [`AlphaMode::colors`] gives the RGB image to process and
+/// [`AlphaMode::apply`] puts alpha back onto the result. Both work at any
+/// channel depth, see [`crate::depth`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum AlphaMode {
+    /// Alpha is carried over unchanged.
+    #[default]
+    Preserve,
+    /// Alpha is ordered dithered against the map, so every pixel ends up
+    /// fully opaque or fully transparent.
+    Dither(ThresholdMap),
+    /// The image is composited onto this color first; the result is opaque.
+    Matte(Rgb<u8>),
+}
+
+impl AlphaMode {
+    /// The colors of `img` to process: composited onto the matte color for
+    /// [`AlphaMode::Matte`], unchanged otherwise.
+    pub fn colors<T: Channel>(
+        &self,
+        img: &ImageBuffer<Rgba<T>, Vec<T>>,
+    ) -> ImageBuffer<Rgb<T>, Vec<T>>
+    where
+        Rgb<T>: Pixel<Subpixel = T>,
+        Rgba<T>: Pixel<Subpixel = T>,
+    {
+        match self {
+            AlphaMode::Matte(color) => matte(img, *color),
+            _ => ImageBuffer::from_fn(img.width(), img.height(), |x, y| {
+                let [r, g, b, _] = img.get_pixel(x, y).0;
+                Rgb([r, g, b])
+            }),
+        }
+    }
+
+    /// Combines `rgb`, the processed colors of `src`, with the alpha of
+    /// `src`, rescaled to the depth of `rgb`; dithering functions e.g. give
+    /// 8-bit colors whatever the depth of their input.
+    ///
+    /// Output pixel `(x, y)` takes the alpha of source pixel
+    /// `(x * step, y * step)`, clamped to the image, which matches the
+    /// sampling of `pixel_size` in the dithering functions. Use a `step` of
+    /// 1 after functions that keep the image size.
+    pub fn apply<T: Channel, U: Channel>(
+        &self,
+        src: &ImageBuffer<Rgba<T>, Vec<T>>,
+        rgb: ImageBuffer<Rgb<U>, Vec<U>>,
+        step: u32,
+    ) -> ImageBuffer<Rgba<U>, Vec<U>>
+    where
+        Rgba<T>: Pixel<Subpixel = T>,
+        Rgb<U>: Pixel<Subpixel = U>,
+        Rgba<U>: Pixel<Subpixel = U>,
+    {
+        let (w, h) = src.dimensions();
+        ImageBuffer::from_fn(rgb.width(), rgb.height(), |x, y| {
+            let [r, g, b] = rgb.get_pixel(x, y).0;
+            let a: f32 = src[((x * step).min(w - 1), (y * step).min(h - 1))][3].value() / T::MAX;
+            let a: f32 = match self {
+                AlphaMode::Preserve => a,
+                AlphaMode::Dither(map) => {
+                    if a > map.value(x, y) {
+                        1.0
+                    } else {
+                        0.0
+                    }
+                }
+                AlphaMode::Matte(_) => 1.0,
+            };
+            Rgba([r, g, b, U::from_value_rounded(a * U::MAX)])
+        })
+    }
+}
+
+/// Composites `img` onto a solid `color`.
+pub fn matte<T: Channel>(
+    img: &ImageBuffer<Rgba<T>, Vec<T>>,
+    color: Rgb<u8>,
+) -> ImageBuffer<Rgb<T>, Vec<T>>
+where
+    Rgb<T>: Pixel<Subpixel = T>,
+    Rgba<T>: Pixel<Subpixel = T>,
+{
+    let color: [T; 3] = color.0.map(from_u8_scale);
+    ImageBuffer::from_fn(img.width(), img.height(), |x, y| {
+        let [r, g, b, a] = img.get_pixel(x, y).0;
+        let a: f32 = a.value() / T::MAX;
+        let blend = |c: T, m: T| T::from_value_rounded(c.value() * a + m.value() * (1.0 - a));
+        Rgb([blend(r, color[0]), blend(g, color[1]), blend(b, color[2])])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbaImage;
+
+    #[test]
+    fn dither_gives_binary_alpha_following_the_map() {
+        let map = ThresholdMap::bayer(2).unwrap();
+        let mode = AlphaMode::Dither(map.clone());
+        for (a, opaque) in [(0, 0), (102, 4), (255, 8)] {
+            let src = RgbaImage::from_pixel(4, 2, Rgba([10, 20, 30, a]));
+            let out = mode.apply(&src, mode.colors(&src), 1);
+            let mut count: usize = 0;
+            for (x, y, pixel) in out.enumerate_pixels() {
+                assert_eq!(pixel.0[..3], [10, 20, 30]);
+                // 102 / 255 = 0.4 lets through the cells ranked 0/4 and 1/4.
+                let expected: u8 = if a as f32 / 255.0 > map.value(x, y) {
+                    255
+                } else {
+                    0
+                };
+                assert_eq!(pixel[3], expected, "alpha {} at ({}, {})", a, x, y);
+                count += (pixel[3] == 255) as usize;
+            }
+            assert_eq!(count, opaque, "alpha {}", a);
+        }
+    }
+
+    #[test]
+    fn matte_composites_and_makes_the_result_opaque() {
+        let mode = AlphaMode::Matte(Rgb([0, 0, 255]));
+        let alphas: [u8; 3] = [255, 128, 0];
+        let src = RgbaImage::from_fn(3, 1, |x, _| Rgba([200, 100, 0, alphas[x as usize]]));
+        let rgb = mode.colors(&src);
+        let expected: [[u8; 3]; 3] = [[200, 100, 0], [100, 50, 127], [0, 0, 255]];
+        for (x, color) in expected.iter().enumerate() {
+            assert_eq!(rgb.get_pixel(x as u32, 0).0, *color);
+        }
+        let out = mode.apply(&src, rgb, 1);
+        assert!(out.pixels().all(|pixel| pixel[3] == 255));
+    }
+}
@@ -0,0 +1,148 @@
+use std::process::ExitCode;
+
+use rust_image_processing::pipeline::{Pipeline, Step};
+use rust_image_processing::*;
+
+const USAGE: &str = "\
+Usage: rust_image-processing <operation> <input> <output> [key=value ...]
+
+Operations:
+";
+
+const NOTES: &str = "\
+Palettes and colors are 6-digit hex values concatenated, e.g. pal=000000ffffff.
+A palette can also be named (game-boy, cga, ega, pico-8, nes, c64,
+zx-spectrum, web-safe), be a .gpl, .ase, .aco, .txt (Paint.NET), .pal (JASC)
+or .hex (Lospec) file, or be extracted from the input with median:<n>,
+kmeans:<n> (seeded by `seed`) or octree:<n>. save_pal=<file> writes the
+palette used by bayer and errprop to any of these file formats.
+Maps: bayer2/4/8/16, clustered<N> and line<N> (N up to 256), blue<N> (N from
+4 to 128, seeded by `seed`) or a path to a .png, .tmap or text matrix.
+Metrics: euclidean, redmean, linear, cie76, ciede2000, oklab.
+editcolor replaces colors with no channel more than measure levels off, or
+within measure under metric= when one is given.
+Images are processed at their own depth, so 16-bit and float files keep
+their precision (bayer and errprop give 8-bit palette colors), and saved in
+the closest form the output format can store.
+Alpha: preserve, dither (against [alpha_map=bayer8]) or matte (onto
+[matte=ffffff]). Transparency is kept by every operation and saved unless
+the output format cannot store it.
+linear=true does the arithmetic in linear light instead of on sRGB values.
+Levels and curve points are fractions of full intensity, 0.0 to 1.0.
+Gradients: pink, grayscale, sepia, cyanotype, fire, ocean, or hex colors
+spread evenly (000000,ff00cc,ffffcc) or placed (0:000000,0.3:ff00cc,1:ffffcc).
+Kernels: floyd-steinberg, jarvis-judice-ninke, stucki, atkinson, burkes,
+sierra-3, sierra-2, sierra-lite.
+ascii writes the input as text to file=<path>, one character of chars
+(darkest first) per k by k*ssr pixels; ssr is the height over the width of
+a character in the font. The image itself is passed through unchanged.
+scalenx, epx, hqx and xbr enlarge pixel art by k = 2, 3 or 4 (epx: 2 or 4),
+smoothing edges where upscale repeats pixels. unscale detects the pixel grid
+of an enlarged sprite and shrinks it back to one pixel per cell.
+A recipe runs a list of the operations above, one [[steps]] table per
+operation in TOML (a \"steps\" array in JSON), e.g.
+  [[steps]]
+  op = \"bayer\"
+  pal = \"pico-8\"";
+
+/// [`USAGE`] with an entry per filter, listing its parameters and
+/// defaults, followed by [`NOTES`].
+fn usage() -> String {
+    let mut text = USAGE.to_string();
+    for filter in filter::FILTERS {
+        let mut line = format!("  {:<10}", filter.name());
+        let mut width = line.len();
+        for spec in filter.params() {
+            let param = match spec.default {
+                Some(default) => format!(" [{}={}]", spec.name, default),
+                None if spec.required => format!(" {}=<{}>", spec.name, spec.kind.placeholder()),
+                None => format!(" [{}=<{}>]", spec.name, spec.kind.placeholder()),
+            };
+            if width + param.len() > 78 {
+                line.push_str("\n            ");
+                width = 12;
+            }
+            width += param.len();
+            line.push_str(&param);
+        }
+        text.push_str(line.trim_end());
+        text.push('\n');
+    }
+    text.push_str("  recipe     file=<recipe.toml|recipe.json>\n\n");
+    text.push_str(NOTES);
+    text
+}
+
+/// The pipeline the command line describes: the steps of a `recipe` file,
+/// or `op` as a single step.
+fn pipeline(op: &str, args: &[String]) -> Result<Pipeline, String> {
+    let mut step = Step::new(op);
+    for arg in args {
+        match arg.split_once('=') {
+            Some((key, val)) => step = step.param(key, val),
+            None => return Err(format!("expected key=value, got `{}`", arg)),
+        }
+    }
+    if op != "recipe" {
+        return Ok(Pipeline::new().then(step));
+    }
+    match step.params.get("file") {
+        Some(path) => Pipeline::load(path.to_string())
+            .map_err(|err| format!("cannot load recipe `{}`: {}", path, err)),
+        None => Err("missing parameter `file`".to_string()),
+    }
+}
+
+pub fn run(args: &[String]) -> ExitCode {
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", usage());
+        return ExitCode::SUCCESS;
+    }
+    let (op, input, output, rest) = match args {
+        [op, input, output, rest @ ..] => (op, input, output, rest),
+        _ => {
+            eprintln!("{}", usage());
+            return ExitCode::from(2);
+        }
+    };
+    let pipeline = match pipeline(op, rest) {
+        Ok(pipeline) => pipeline,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            return ExitCode::from(2);
+        }
+    };
+    let img = match open_img_dynamic(input) {
+        Ok(img) => img,
+        Err(err) => {
+            eprintln!("error: cannot open `{}`: {}", input, err);
+            return ExitCode::FAILURE;
+        }
+    };
+    let img = match pipeline.run(img) {
+        Ok(img) => img,
+        Err(Error::Step { source, .. }) if op != "recipe" => {
+            eprintln!("error: {}", source);
+            return exit_code(&source);
+        }
+        Err(err) => {
+            eprintln!("error: {}", err);
+            return exit_code(&err);
+        }
+    };
+    if let Err(err) = save_img(img, output) {
+        eprintln!("error: cannot save `{}`: {}", output, err);
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
+
+/// 1 for files that cannot be read or written, 2 for everything the
+/// arguments got wrong.
+fn exit_code(err: &Error) -> ExitCode {
+    match err {
+        Error::Io(_) => ExitCode::FAILURE,
+        Error::Step { source, .. } => exit_code(source),
+        _ => ExitCode::from(2),
+    }
+}
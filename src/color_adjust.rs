@@ -0,0 +1,96 @@
+use crate::depth::Channel;
+use crate::par;
+use crate::types_n_convs::{hsl_to_rgb, hsv_to_rgb, rgb_to_hsl, rgb_to_hsv};
+use image::{ImageBuffer, Pixel, Rgb};
+
+/// Runs `f` on every pixel as channels in `0.0..=1.0`, rounding the result
+/// back for integer depths.
+fn map_normalized<T: Channel, F>(img: &mut ImageBuffer<Rgb<T>, Vec<T>>, f: F)
+where
+    Rgb<T>: Pixel<Subpixel = T>,
+    F: Fn([f32; 3]) -> [f32; 3] + Send + Sync,
+{
+    par::for_each_pixel(img, |pixel| {
+        pixel.0 = f(pixel.0.map(|v| v.value() / T::MAX)).map(|v| T::from_value_rounded(v * T::MAX))
+    });
+}
+
+/// Rec. 709 luma of channels in `0.0..=1.0`.
+fn luma([r, g, b]: [f32; 3]) -> f32 {
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+/// Rotates the hue by `hue` degrees, multiplies the HSL saturation by
+/// `saturation` and shifts the lightness by `lightness`, a fraction of full
+/// intensity. `0.0`, `1.0` and `0.0` leave the image unchanged.
+pub fn hsl_adjust<T: Channel>(
+    img: &mut ImageBuffer<Rgb<T>, Vec<T>>,
+    hue: f32,
+    saturation: f32,
+    lightness: f32,
+) where
+    Rgb<T>: Pixel<Subpixel = T>,
+{
+    map_normalized(img, |rgb| {
+        let [h, s, l] = rgb_to_hsl(rgb);
+        hsl_to_rgb([
+            h + hue,
+            (s * saturation).clamp(0.0, 1.0),
+            (l + lightness).clamp(0.0, 1.0),
+        ])
+    });
+}
+
+/// Rotates the hue by `hue` degrees and multiplies the HSV saturation and
+/// value by `saturation` and `value`. `0.0`, `1.0` and `1.0` leave the image
+/// unchanged.
+pub fn hsv_adjust<T: Channel>(
+    img: &mut ImageBuffer<Rgb<T>, Vec<T>>,
+    hue: f32,
+    saturation: f32,
+    value: f32,
+) where
+    Rgb<T>: Pixel<Subpixel = T>,
+{
+    map_normalized(img, |rgb| {
+        let [h, s, v] = rgb_to_hsv(rgb);
+        hsv_to_rgb([h + hue, (s * saturation).clamp(0.0, 1.0), v * value])
+    });
+}
+
+/// Saturation boost that favours dull colors: each pixel is pushed away
+/// from its luma by `amount` times how unsaturated it is, so skin tones and
+/// already vivid colors change little. Negative amounts desaturate.
+pub fn vibrance<T: Channel>(img: &mut ImageBuffer<Rgb<T>, Vec<T>>, amount: f32)
+where
+    Rgb<T>: Pixel<Subpixel = T>,
+{
+    map_normalized(img, |rgb| {
+        let [_, s, _] = rgb_to_hsv(rgb);
+        let y: f32 = luma(rgb);
+        let f: f32 = 1.0 + amount * (1.0 - s);
+        rgb.map(|c| y + (c - y) * f)
+    });
+}
+
+/// Shifts the channels of shadows, midtones and highlights separately.
+///
+/// Each shift is a fraction of full intensity per channel, so
+/// `[0.1, 0.0, -0.1]` warms a range towards red and away from blue. A
+/// pixel's luma decides how much of each range applies: shadows fade out
+/// and highlights fade in towards white, the midtones peaking at half
+/// strength in between.
+pub fn color_balance<T: Channel>(
+    img: &mut ImageBuffer<Rgb<T>, Vec<T>>,
+    shadows: [f32; 3],
+    midtones: [f32; 3],
+    highlights: [f32; 3],
+) where
+    Rgb<T>: Pixel<Subpixel = T>,
+{
+    map_normalized(img, |rgb| {
+        let l: f32 = luma(rgb).clamp(0.0, 1.0);
+        let (ws, wm, wh) = ((1.0 - l) * (1.0 - l), 2.0 * l * (1.0 - l), l * l);
+        [0, 1, 2].map(|c| rgb[c] + ws * shadows[c] + wm * midtones[c] + wh * highlights[c])
+    });
+}
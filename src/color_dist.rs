@@ -0,0 +1,184 @@
+use crate::types_n_convs::*;
+use std::f32::consts::PI;
+
+/// How the distance between two sRGB colors is measured when matching
+/// pixels against a palette.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorMetric {
+    /// Plain Euclidean distance of the sRGB bytes.
+    #[default]
+    Euclidean,
+    /// Euclidean distance with channel weights depending on the mean red
+    /// value ("redmean"), a cheap perceptual approximation.
+    Redmean,
+    /// Euclidean distance of the linear light values, scaled to `0..255`.
+    LinearRgb,
+    /// CIE 1976 ΔE: Euclidean distance in CIELAB.
+    Cie76,
+    /// CIEDE2000 ΔE.
+    Ciede2000,
+    /// Euclidean distance in Oklab, scaled by 100 to be comparable with ΔE.
+    Oklab,
+}
+
+impl ColorMetric {
+    pub const ALL: [ColorMetric; 6] = [
+        ColorMetric::Euclidean,
+        ColorMetric::Redmean,
+        ColorMetric::LinearRgb,
+        ColorMetric::Cie76,
+        ColorMetric::Ciede2000,
+        ColorMetric::Oklab,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorMetric::Euclidean => "euclidean",
+            ColorMetric::Redmean => "redmean",
+            ColorMetric::LinearRgb => "linear",
+            ColorMetric::Cie76 => "cie76",
+            ColorMetric::Ciede2000 => "ciede2000",
+            ColorMetric::Oklab => "oklab",
+        }
+    }
+
+    /// Looks a metric up by its [`ColorMetric::name`], ignoring case.
+    pub fn by_name(name: &str) -> Option<ColorMetric> {
+        ColorMetric::ALL
+            .into_iter()
+            .find(|metric| metric.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Distance between `one` and `two` under this metric.
+    pub fn dist(self, one: [u8; 3], two: [u8; 3]) -> f32 {
+        self.key(one, two).sqrt()
+    }
+
+    /// Coordinates in which this metric is plain Euclidean distance, or
+    /// `None` for `Redmean` and `Ciede2000`, which have no such space.
+    pub fn coords(self, color: [u8; 3]) -> Option<[f32; 3]> {
+        match self {
+            ColorMetric::Euclidean => Some([color[0] as f32, color[1] as f32, color[2] as f32]),
+            ColorMetric::LinearRgb => Some(rgb_to_linear(color).map(|v| 255.0 * v)),
+            ColorMetric::Cie76 => Some(rgb_to_lab(color)),
+            ColorMetric::Oklab => Some(rgb_to_oklab(color).map(|v| 100.0 * v)),
+            ColorMetric::Redmean | ColorMetric::Ciede2000 => None,
+        }
+    }
+
+    /// The squared [`ColorMetric::dist`], ordered like it and, where
+    /// [`ColorMetric::coords`] exist, cheaper to compute. Palette searches compare keys so every search strategy
+    /// agrees exactly, and the k-d tree of [`crate::nearest`] prunes by
+    /// comparing them with squared distances along one axis.
+    pub(crate) fn key(self, one: [u8; 3], two: [u8; 3]) -> f32 {
+        match self {
+            ColorMetric::Redmean => {
+                let rmean: f32 = (one[0] as f32 + two[0] as f32) / 2.0;
+                let dr: f32 = i32_sub(one[0], two[0]) as f32;
+                let dg: f32 = i32_sub(one[1], two[1]) as f32;
+                let db: f32 = i32_sub(one[2], two[2]) as f32;
+                (2.0 + rmean / 256.0) * dr * dr
+                    + 4.0 * dg * dg
+                    + (2.0 + (255.0 - rmean) / 256.0) * db * db
+            }
+            ColorMetric::Ciede2000 => ciede2000(rgb_to_lab(one), rgb_to_lab(two)).powi(2),
+            _ => dist2(
+                self.coords(one).expect("metric has coordinates"),
+                self.coords(two).expect("metric has coordinates"),
+            ),
+        }
+    }
+}
+
+/// Squared Euclidean distance.
+pub(crate) fn dist2(one: [f32; 3], two: [f32; 3]) -> f32 {
+    (one[0] - two[0]).powi(2) + (one[1] - two[1]).powi(2) + (one[2] - two[2]).powi(2)
+}
+
+/// CIEDE2000 color difference of two CIELAB colors, following Sharma, Wu
+/// and Dalal (2005) with all weighting factors set to 1.
+fn ciede2000(lab1: [f32; 3], lab2: [f32; 3]) -> f32 {
+    let [l1, a1, b1] = lab1;
+    let [l2, a2, b2] = lab2;
+    let c_mean: f32 = ((a1 * a1 + b1 * b1).sqrt() + (a2 * a2 + b2 * b2).sqrt()) / 2.0;
+    let c_mean7: f32 = c_mean.powi(7);
+    let g: f32 = 0.5 * (1.0 - (c_mean7 / (c_mean7 + 25.0_f32.powi(7))).sqrt());
+    let (a1, a2) = (a1 * (1.0 + g), a2 * (1.0 + g));
+    let (c1, c2) = ((a1 * a1 + b1 * b1).sqrt(), (a2 * a2 + b2 * b2).sqrt());
+    let hue = |b: f32, a: f32| {
+        if a == 0.0 && b == 0.0 {
+            0.0
+        } else {
+            b.atan2(a).rem_euclid(2.0 * PI)
+        }
+    };
+    let (h1, h2) = (hue(b1, a1), hue(b2, a2));
+
+    let dl: f32 = l2 - l1;
+    let dc: f32 = c2 - c1;
+    let dh: f32 = if c1 * c2 == 0.0 {
+        0.0
+    } else if (h2 - h1).abs() <= PI {
+        h2 - h1
+    } else if h2 - h1 > PI {
+        h2 - h1 - 2.0 * PI
+    } else {
+        h2 - h1 + 2.0 * PI
+    };
+    let dh: f32 = 2.0 * (c1 * c2).sqrt() * (dh / 2.0).sin();
+
+    let l_mean: f32 = (l1 + l2) / 2.0;
+    let c_mean: f32 = (c1 + c2) / 2.0;
+    let h_mean: f32 = if c1 * c2 == 0.0 {
+        h1 + h2
+    } else if (h1 - h2).abs() <= PI {
+        (h1 + h2) / 2.0
+    } else if h1 + h2 < 2.0 * PI {
+        (h1 + h2 + 2.0 * PI) / 2.0
+    } else {
+        (h1 + h2 - 2.0 * PI) / 2.0
+    };
+    let t: f32 = 1.0 - 0.17 * (h_mean - PI / 6.0).cos()
+        + 0.24 * (2.0 * h_mean).cos()
+        + 0.32 * (3.0 * h_mean + PI / 30.0).cos()
+        - 0.20 * (4.0 * h_mean - 63.0 * PI / 180.0).cos();
+    let d_theta: f32 = PI / 6.0 * (-((h_mean * 180.0 / PI - 275.0) / 25.0).powi(2)).exp();
+    let c_mean7: f32 = c_mean.powi(7);
+    let r_c: f32 = 2.0 * (c_mean7 / (c_mean7 + 25.0_f32.powi(7))).sqrt();
+    let l50: f32 = (l_mean - 50.0).powi(2);
+    let s_l: f32 = 1.0 + 0.015 * l50 / (20.0 + l50).sqrt();
+    let s_c: f32 = 1.0 + 0.045 * c_mean;
+    let s_h: f32 = 1.0 + 0.015 * c_mean * t;
+    let r_t: f32 = -(2.0 * d_theta).sin() * r_c;
+
+    ((dl / s_l).powi(2) + (dc / s_c).powi(2) + (dh / s_h).powi(2) + r_t * (dc / s_c) * (dh / s_h))
+        .sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::SplitMix64;
+
+    #[test]
+    fn keys_are_squared_distances() {
+        let mut rng = SplitMix64::new(11);
+        let mut color = || [0; 3].map(|_| rng.below(256) as u8);
+        for _ in 0..200 {
+            let (one, two) = (color(), color());
+            for metric in ColorMetric::ALL {
+                let (key, dist) = (metric.key(one, two), metric.dist(one, two));
+                assert!(key >= 0.0, "{:?}", metric);
+                assert!(
+                    (key - dist * dist).abs() <= 1e-3 * key.max(1.0),
+                    "{:?}",
+                    metric
+                );
+                if let Some((a, b)) = metric.coords(one).zip(metric.coords(two)) {
+                    assert_eq!(key, dist2(a, b), "{:?}", metric);
+                }
+            }
+        }
+        assert_eq!(ColorMetric::Redmean.dist([0, 0, 0], [0, 10, 0]), 20.0);
+    }
+}
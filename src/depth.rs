@@ -0,0 +1,163 @@
+use crate::error::Error;
+use image::{DynamicImage, ImageBuffer, Pixel, Primitive, Rgb, Rgba};
+use std::path::Path;
+
+/// Channel types the depth-generic functions accept: `u8`, `u16` and `f32`.
+///
+/// Values are worked on as `f32` in the type's own scale, `0..=MAX`.
+/// Integer channels truncate and saturate when converted back, like the
+/// `u8_*` helpers, so 8-bit results stay what they always were. `f32`
+/// channels are neither clamped nor rounded, so a chain of operations on a
+/// float buffer only quantizes once, when it is converted or saved.
+pub trait Channel: Primitive + Send + Sync + 'static {
+    /// Full intensity: 255, 65535 or 1.0.
+    const MAX: f32;
+
+    fn value(self) -> f32;
+
+    fn from_value(v: f32) -> Self;
+
+    /// Like [`Channel::from_value`], but rounding to the nearest integer
+    /// value, for curves that should map every level exactly onto itself.
+    fn from_value_rounded(v: f32) -> Self;
+
+    /// Decodes `img` into RGB of this depth.
+    fn rgb_from_dynamic(img: DynamicImage) -> ImageBuffer<Rgb<Self>, Vec<Self>>
+    where
+        Rgb<Self>: Pixel<Subpixel = Self>;
+
+    fn rgb_into_dynamic(img: ImageBuffer<Rgb<Self>, Vec<Self>>) -> DynamicImage
+    where
+        Rgb<Self>: Pixel<Subpixel = Self>;
+
+    /// Decodes `img` into RGBA of this depth.
+    fn rgba_from_dynamic(img: DynamicImage) -> ImageBuffer<Rgba<Self>, Vec<Self>>
+    where
+        Rgba<Self>: Pixel<Subpixel = Self>;
+
+    fn rgba_into_dynamic(img: ImageBuffer<Rgba<Self>, Vec<Self>>) -> DynamicImage
+    where
+        Rgba<Self>: Pixel<Subpixel = Self>;
+}
+
+impl Channel for u8 {
+    const MAX: f32 = 255.0;
+
+    fn value(self) -> f32 {
+        self as f32
+    }
+    fn from_value(v: f32) -> Self {
+        v as u8
+    }
+    fn from_value_rounded(v: f32) -> Self {
+        v.round() as u8
+    }
+    fn rgb_from_dynamic(img: DynamicImage) -> ImageBuffer<Rgb<Self>, Vec<Self>> {
+        img.into_rgb8()
+    }
+    fn rgb_into_dynamic(img: ImageBuffer<Rgb<Self>, Vec<Self>>) -> DynamicImage {
+        DynamicImage::ImageRgb8(img)
+    }
+    fn rgba_from_dynamic(img: DynamicImage) -> ImageBuffer<Rgba<Self>, Vec<Self>> {
+        img.into_rgba8()
+    }
+    fn rgba_into_dynamic(img: ImageBuffer<Rgba<Self>, Vec<Self>>) -> DynamicImage {
+        DynamicImage::ImageRgba8(img)
+    }
+}
+
+impl Channel for u16 {
+    const MAX: f32 = 65535.0;
+
+    fn value(self) -> f32 {
+        self as f32
+    }
+    fn from_value(v: f32) -> Self {
+        v as u16
+    }
+    fn from_value_rounded(v: f32) -> Self {
+        v.round() as u16
+    }
+    fn rgb_from_dynamic(img: DynamicImage) -> ImageBuffer<Rgb<Self>, Vec<Self>> {
+        img.into_rgb16()
+    }
+    fn rgb_into_dynamic(img: ImageBuffer<Rgb<Self>, Vec<Self>>) -> DynamicImage {
+        DynamicImage::ImageRgb16(img)
+    }
+    fn rgba_from_dynamic(img: DynamicImage) -> ImageBuffer<Rgba<Self>, Vec<Self>> {
+        img.into_rgba16()
+    }
+    fn rgba_into_dynamic(img: ImageBuffer<Rgba<Self>, Vec<Self>>) -> DynamicImage {
+        DynamicImage::ImageRgba16(img)
+    }
+}
+
+impl Channel for f32 {
+    const MAX: f32 = 1.0;
+
+    fn value(self) -> f32 {
+        self
+    }
+    fn from_value(v: f32) -> Self {
+        v
+    }
+    fn from_value_rounded(v: f32) -> Self {
+        v
+    }
+    fn rgb_from_dynamic(img: DynamicImage) -> ImageBuffer<Rgb<Self>, Vec<Self>> {
+        img.into_rgb32f()
+    }
+    fn rgb_into_dynamic(img: ImageBuffer<Rgb<Self>, Vec<Self>>) -> DynamicImage {
+        DynamicImage::ImageRgb32F(img)
+    }
+    fn rgba_from_dynamic(img: DynamicImage) -> ImageBuffer<Rgba<Self>, Vec<Self>> {
+        img.into_rgba32f()
+    }
+    fn rgba_into_dynamic(img: ImageBuffer<Rgba<Self>, Vec<Self>>) -> DynamicImage {
+        DynamicImage::ImageRgba32F(img)
+    }
+}
+
+/// Converts `img` to another channel depth, rescaling the values. Float
+/// values outside `0.0..=1.0` are clamped when going to an integer depth.
+pub fn convert_depth<S: Channel, T: Channel>(
+    img: ImageBuffer<Rgb<S>, Vec<S>>,
+) -> ImageBuffer<Rgb<T>, Vec<T>>
+where
+    Rgb<T>: Pixel<Subpixel = T>,
+    Rgb<S>: Pixel<Subpixel = S>,
+{
+    T::rgb_from_dynamic(S::rgb_into_dynamic(img))
+}
+
+/// Opens and decodes the image at `path` as RGB of depth `T`, e.g. `u16` to
+/// keep the precision of 16-bit PNG and TIFF files.
+pub fn open_img_as<T: Channel, P: AsRef<Path>>(
+    path: P,
+) -> Result<ImageBuffer<Rgb<T>, Vec<T>>, Error>
+where
+    Rgb<T>: Pixel<Subpixel = T>,
+{
+    Ok(T::rgb_from_dynamic(
+        image::ImageReader::open(path)?
+            .decode()
+            .map_err(Error::decode)?,
+    ))
+}
+
+/// The channel `v` of depth `T` on the `0..255` scale of palette colors.
+pub(crate) fn to_u8_scale<T: Channel>(v: T) -> f32
+where
+    Rgb<T>: Pixel<Subpixel = T>,
+{
+    v.value() * 255.0 / T::MAX
+}
+
+/// The 8-bit channel `v` at depth `T`, e.g. a palette color for a 16-bit
+/// image.
+pub(crate) fn from_u8_scale<T: Channel>(v: u8) -> T
+where
+    Rgb<T>: Pixel<Subpixel = T>,
+{
+    T::from_value_rounded(v as f32 * T::MAX / 255.0)
+}
@@ -0,0 +1,238 @@
+use crate::types_n_convs::normalize_name;
+
+/// An error diffusion kernel.
+///
+/// Each entry of `weights` is `(dx, dy, weight)`: the share of the
+/// quantization error pushed to the pixel `dx` columns right and `dy` rows
+/// below the current one is `weight / divisor`.
+pub struct DiffusionKernel {
+    pub name: &'static str,
+    pub weights: &'static [(i32, u32, f32)],
+    pub divisor: f32,
+}
+
+pub const FLOYD_STEINBERG: DiffusionKernel = DiffusionKernel {
+    name: "floyd-steinberg",
+    weights: &[(1, 0, 7.0), (-1, 1, 3.0), (0, 1, 5.0), (1, 1, 1.0)],
+    divisor: 16.0,
+};
+
+pub const JARVIS_JUDICE_NINKE: DiffusionKernel = DiffusionKernel {
+    name: "jarvis-judice-ninke",
+    weights: &[
+        (1, 0, 7.0),
+        (2, 0, 5.0),
+        (-2, 1, 3.0),
+        (-1, 1, 5.0),
+        (0, 1, 7.0),
+        (1, 1, 5.0),
+        (2, 1, 3.0),
+        (-2, 2, 1.0),
+        (-1, 2, 3.0),
+        (0, 2, 5.0),
+        (1, 2, 3.0),
+        (2, 2, 1.0),
+    ],
+    divisor: 48.0,
+};
+
+pub const STUCKI: DiffusionKernel = DiffusionKernel {
+    name: "stucki",
+    weights: &[
+        (1, 0, 8.0),
+        (2, 0, 4.0),
+        (-2, 1, 2.0),
+        (-1, 1, 4.0),
+        (0, 1, 8.0),
+        (1, 1, 4.0),
+        (2, 1, 2.0),
+        (-2, 2, 1.0),
+        (-1, 2, 2.0),
+        (0, 2, 4.0),
+        (1, 2, 2.0),
+        (2, 2, 1.0),
+    ],
+    divisor: 42.0,
+};
+
+/// Only diffuses 6/8 of the error, trading detail in flat areas for contrast.
+pub const ATKINSON: DiffusionKernel = DiffusionKernel {
+    name: "atkinson",
+    weights: &[
+        (1, 0, 1.0),
+        (2, 0, 1.0),
+        (-1, 1, 1.0),
+        (0, 1, 1.0),
+        (1, 1, 1.0),
+        (0, 2, 1.0),
+    ],
+    divisor: 8.0,
+};
+
+pub const BURKES: DiffusionKernel = DiffusionKernel {
+    name: "burkes",
+    weights: &[
+        (1, 0, 8.0),
+        (2, 0, 4.0),
+        (-2, 1, 2.0),
+        (-1, 1, 4.0),
+        (0, 1, 8.0),
+        (1, 1, 4.0),
+        (2, 1, 2.0),
+    ],
+    divisor: 32.0,
+};
+
+pub const SIERRA_3: DiffusionKernel = DiffusionKernel {
+    name: "sierra-3",
+    weights: &[
+        (1, 0, 5.0),
+        (2, 0, 3.0),
+        (-2, 1, 2.0),
+        (-1, 1, 4.0),
+        (0, 1, 5.0),
+        (1, 1, 4.0),
+        (2, 1, 2.0),
+        (-1, 2, 2.0),
+        (0, 2, 3.0),
+        (1, 2, 2.0),
+    ],
+    divisor: 32.0,
+};
+
+pub const SIERRA_2: DiffusionKernel = DiffusionKernel {
+    name: "sierra-2",
+    weights: &[
+        (1, 0, 4.0),
+        (2, 0, 3.0),
+        (-2, 1, 1.0),
+        (-1, 1, 2.0),
+        (0, 1, 3.0),
+        (1, 1, 2.0),
+        (2, 1, 1.0),
+    ],
+    divisor: 16.0,
+};
+
+pub const SIERRA_LITE: DiffusionKernel = DiffusionKernel {
+    name: "sierra-lite",
+    weights: &[(1, 0, 2.0), (-1, 1, 1.0), (0, 1, 1.0)],
+    divisor: 4.0,
+};
+
+pub const KERNELS: [&DiffusionKernel; 8] = [
+    &FLOYD_STEINBERG,
+    &JARVIS_JUDICE_NINKE,
+    &STUCKI,
+    &ATKINSON,
+    &BURKES,
+    &SIERRA_3,
+    &SIERRA_2,
+    &SIERRA_LITE,
+];
+
+impl DiffusionKernel {
+    /// Looks a kernel up by name, ignoring case and `-`/`_`/space separators.
+    ///
+    /// Besides the names in [`KERNELS`], `fs`, `jjn` and `sierra` (for
+    /// Sierra 3) are accepted.
+    pub fn by_name(name: &str) -> Option<&'static DiffusionKernel> {
+        match normalize_name(name).as_str() {
+            "fs" => Some(&FLOYD_STEINBERG),
+            "jjn" => Some(&JARVIS_JUDICE_NINKE),
+            "sierra" => Some(&SIERRA_3),
+            norm => KERNELS
+                .into_iter()
+                .find(|kernel| normalize_name(kernel.name) == norm),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{twod_errprop_dithering, ColorMetric, PaletteMatcher};
+    use image::{Rgb, RgbImage};
+
+    /// A 6x4 image of grey 85 dithered to black and white, as `#` and `.`
+    /// per pixel.
+    fn dither_grey(kernel: &DiffusionKernel) -> Vec<String> {
+        let pal =
+            PaletteMatcher::new(vec![[0, 0, 0], [255, 255, 255]], ColorMetric::Euclidean).unwrap();
+        let img = RgbImage::from_pixel(6, 4, Rgb([85, 85, 85]));
+        let out = twod_errprop_dithering(img, &pal, kernel, false, false, 1, 1.0, 1.0);
+        out.rows()
+            .map(|row| {
+                row.map(|pixel| if pixel[0] == 255 { '#' } else { '.' })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// The expected patterns follow the published weights by hand: e.g. for
+    /// Floyd-Steinberg the first pixel goes black leaving an error of 85,
+    /// the second gets 85 + 85 * 7/16 = 122.2 and goes black too, the third
+    /// gets 85 + 122.2 * 7/16 = 138.5 and goes white.
+    #[test]
+    fn kernels_match_the_reference_patterns() {
+        let expected: [(&DiffusionKernel, [&str; 4]); 8] = [
+            (&FLOYD_STEINBERG, ["..#..#", "#..#..", ".#..#.", "..#..#"]),
+            (
+                &JARVIS_JUDICE_NINKE,
+                ["......", ".##.#.", "...#..", "..#..#"],
+            ),
+            (&STUCKI, ["......", ".##.#.", "...#.#", ".#...."]),
+            (&ATKINSON, ["......", ".#..#.", "..#..#", "..#..."]),
+            (&BURKES, ["...#..", ".#..#.", "..#..#", ".#..#."]),
+            (&SIERRA_3, ["......", ".##.#.", "...#..", ".#..#."]),
+            (&SIERRA_2, ["..#...", ".#..#.", "..#..#", ".#..#."]),
+            (&SIERRA_LITE, [".#..#.", "..#..#", "#..#..", ".#..#."]),
+        ];
+        for (kernel, pattern) in expected {
+            assert_eq!(dither_grey(kernel), pattern, "{}", kernel.name);
+        }
+    }
+
+    /// With a kernel pushing all the error one pixel right, on a 3x2 image
+    /// with columns of 100, 100 and 0: the first row goes 100 -> black
+    /// (error 100), 200 -> white (error -55), -55 -> black. Scanned right to
+    /// left with the kernel mirrored, the second row goes 0 -> black, 100 ->
+    /// black (error 100 pushed left), 200 -> white. Raster order repeats the
+    /// first row instead.
+    #[test]
+    fn serpentine_scans_odd_rows_backwards_with_a_mirrored_kernel() {
+        let pal =
+            PaletteMatcher::new(vec![[0, 0, 0], [255, 255, 255]], ColorMetric::Euclidean).unwrap();
+        let kernel = DiffusionKernel {
+            name: "right",
+            weights: &[(1, 0, 1.0)],
+            divisor: 1.0,
+        };
+        let img = RgbImage::from_fn(3, 2, |x, _| Rgb([[100, 100, 0][x as usize]; 3]));
+        let pattern = |serpentine: bool| -> Vec<String> {
+            let out =
+                twod_errprop_dithering(img.clone(), &pal, &kernel, serpentine, false, 1, 1.0, 1.0);
+            out.rows()
+                .map(|row| {
+                    row.map(|pixel| if pixel[0] == 255 { '#' } else { '.' })
+                        .collect()
+                })
+                .collect()
+        };
+        assert_eq!(pattern(false), [".#.", ".#."]);
+        assert_eq!(pattern(true), [".#.", "#.."]);
+    }
+
+    #[test]
+    fn weights_sum_to_the_divisor() {
+        for kernel in KERNELS {
+            let sum: f32 = kernel.weights.iter().map(|&(_, _, w)| w).sum();
+            let expected: f32 = if kernel.name == ATKINSON.name {
+                6.0
+            } else {
+                kernel.divisor
+            };
+            assert_eq!(sum, expected, "{}", kernel.name);
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use std::fmt;
+use std::io;
+
+use image::ImageError;
+
+/// The error of every fallible function in this crate.
+#[derive(Debug)]
+pub enum Error {
+    /// A file could not be read or written.
+    Io(io::Error),
+    /// An image file could not be decoded, e.g. a corrupt file or an
+    /// unsupported format.
+    Decode(ImageError),
+    /// An image could not be encoded, e.g. to a format without a known
+    /// extension.
+    Encode(ImageError),
+    /// A palette is empty or malformed: bad hex colors or a palette file
+    /// that does not follow its format.
+    InvalidPalette(String),
+    /// A parameter is missing, malformed or out of range. Bad threshold
+    /// maps, curves, gradients and recipes are reported as this too.
+    InvalidParameter(String),
+    /// Step `index` (from 0) of a [`crate::Pipeline`] failed with `source`.
+    Step {
+        index: usize,
+        op: String,
+        source: Box<Error>,
+    },
+}
+
+impl Error {
+    /// Files that cannot be opened are [`Error::Io`], the rest of what goes
+    /// wrong while opening an image is [`Error::Decode`].
+    pub(crate) fn decode(err: ImageError) -> Error {
+        match err {
+            ImageError::IoError(err) => Error::Io(err),
+            err => Error::Decode(err),
+        }
+    }
+
+    /// Like [`Error::decode`], for saving.
+    pub(crate) fn encode(err: ImageError) -> Error {
+        match err {
+            ImageError::IoError(err) => Error::Io(err),
+            err => Error::Encode(err),
+        }
+    }
+
+    /// Prefixes the message with `context`, e.g. the parameter a file was
+    /// given for, keeping the kind of error.
+    pub(crate) fn context(self, context: &str) -> Error {
+        match self {
+            Error::Io(err) => {
+                Error::Io(io::Error::new(err.kind(), format!("{}: {}", context, err)))
+            }
+            Error::InvalidPalette(message) => {
+                Error::InvalidPalette(format!("{}: {}", context, message))
+            }
+            Error::InvalidParameter(message) => {
+                Error::InvalidParameter(format!("{}: {}", context, message))
+            }
+            err => err,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "{}", err),
+            Error::Decode(err) | Error::Encode(err) => write!(f, "{}", err),
+            Error::InvalidPalette(message) | Error::InvalidParameter(message) => {
+                write!(f, "{}", message)
+            }
+            Error::Step { index, op, source } => {
+                write!(f, "step {} ({}): {}", index + 1, op, source)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            Error::Decode(err) | Error::Encode(err) => Some(err),
+            Error::Step { source, .. } => Some(source.as_ref()),
+            Error::InvalidPalette(_) | Error::InvalidParameter(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
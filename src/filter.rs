@@ -0,0 +1,1406 @@
+use std::collections::HashMap;
+
+use image::{DynamicImage, ImageBuffer, Pixel, Rgb, RgbImage, Rgba};
+
+use crate::depth::from_u8_scale;
+use crate::diffusion::FLOYD_STEINBERG;
+use crate::palette_io::{self, PaletteFormat};
+use crate::resample::{self, FitMode, ResampleFilter};
+use crate::*;
+
+/// The type of a filter parameter, as it is written on the command line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ParamKind {
+    /// `true` or `false`.
+    Bool,
+    /// An integer in `min..=max`.
+    Int { min: i64, max: i64 },
+    /// A number in `min..=max`.
+    Float { min: f64, max: f64 },
+    /// One of the listed words.
+    Choice(&'static [&'static str]),
+    /// A 6-digit hex color.
+    Color,
+    /// `from:to` pairs of hex colors, separated by commas.
+    ColorPairs,
+    /// A palette: hex colors, a name from [`crate::palettes`], a palette
+    /// file or `median:<n>`, `kmeans:<n>` or `octree:<n>`.
+    Palette,
+    /// A threshold map name or file, see [`ThresholdMap`].
+    Map,
+    /// An error diffusion kernel name, see [`DiffusionKernel::by_name`].
+    Kernel,
+    /// A color metric name, see [`ColorMetric::by_name`].
+    Metric,
+    /// A path to write to.
+    File,
+    /// Free text, e.g. the characters of an ASCII art ramp.
+    Text,
+    /// `x:y` points of a [`Curve`], separated by commas.
+    Curve,
+    /// One number per channel, `r,g,b`.
+    Channels,
+    /// A [`Gradient`] preset name or hex color stops, see
+    /// [`Gradient::parse`].
+    Gradient,
+}
+
+impl ParamKind {
+    /// Short description of the value, e.g. `int` or `preserve|dither`.
+    pub fn placeholder(&self) -> String {
+        match self {
+            ParamKind::Bool => "bool".to_string(),
+            ParamKind::Int { .. } => "int".to_string(),
+            ParamKind::Float { .. } => "float".to_string(),
+            ParamKind::Choice(choices) => choices.join("|"),
+            ParamKind::Color => "hex".to_string(),
+            ParamKind::ColorPairs => "from:to,...".to_string(),
+            ParamKind::Palette => "palette".to_string(),
+            ParamKind::Map => "map".to_string(),
+            ParamKind::Kernel => "kernel".to_string(),
+            ParamKind::Metric => "metric".to_string(),
+            ParamKind::File => "file".to_string(),
+            ParamKind::Text => "text".to_string(),
+            ParamKind::Curve => "x:y,...".to_string(),
+            ParamKind::Channels => "r,g,b".to_string(),
+            ParamKind::Gradient => "gradient".to_string(),
+        }
+    }
+}
+
+/// Description of one parameter of a [`Filter`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ParamSpec {
+    pub name: &'static str,
+    pub kind: ParamKind,
+    /// Value used when the parameter is not given, in command line form.
+    pub default: Option<&'static str>,
+    /// Whether the filter fails without the parameter. Parameters that are
+    /// neither required nor defaulted switch something on when given.
+    pub required: bool,
+}
+
+impl ParamSpec {
+    pub const fn required(name: &'static str, kind: ParamKind) -> ParamSpec {
+        ParamSpec {
+            name,
+            kind,
+            default: None,
+            required: true,
+        }
+    }
+
+    pub const fn with_default(
+        name: &'static str,
+        kind: ParamKind,
+        default: &'static str,
+    ) -> ParamSpec {
+        ParamSpec {
+            name,
+            kind,
+            default: Some(default),
+            required: false,
+        }
+    }
+
+    pub const fn optional(name: &'static str, kind: ParamKind) -> ParamSpec {
+        ParamSpec {
+            name,
+            kind,
+            default: None,
+            required: false,
+        }
+    }
+
+    /// Checks that `val` is of this parameter's kind and within its range.
+    fn check(&self, val: &str) -> Result<(), Error> {
+        let malformed = || invalid(format!("invalid value for `{}`: `{}`", self.name, val));
+        match self.kind {
+            ParamKind::Bool => {
+                val.parse::<bool>().map_err(|_| malformed())?;
+            }
+            ParamKind::Int { min, max } => {
+                let v: i64 = val.parse().map_err(|_| malformed())?;
+                if v < min || v > max {
+                    return Err(out_of_range(self.name, min, max, i64::MAX));
+                }
+            }
+            ParamKind::Float { min, max } => {
+                let v: f64 = val.parse().map_err(|_| malformed())?;
+                if !(min..=max).contains(&v) {
+                    return Err(out_of_range(self.name, min, max, f64::INFINITY));
+                }
+            }
+            ParamKind::Map => {
+                threshold::check_name(val).map_err(|err| {
+                    err.context(&format!("invalid map for `{}`: `{}`", self.name, val))
+                })?;
+            }
+            ParamKind::Choice(choices) if !choices.contains(&val) => {
+                return Err(invalid(format!(
+                    "unknown value for `{}`: `{}`",
+                    self.name, val
+                )));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+fn out_of_range<T: PartialEq + std::fmt::Display>(name: &str, min: T, max: T, top: T) -> Error {
+    invalid(if max == top {
+        format!("`{}` must be at least {}", name, min)
+    } else {
+        format!("`{}` must be between {} and {}", name, min, max)
+    })
+}
+
+fn invalid(message: String) -> Error {
+    Error::InvalidParameter(message)
+}
+
+/// An image operation that describes its parameters, so it can be listed
+/// and driven generically by the command line, pipelines or a UI.
+///
+/// Filters work on RGBA at the depth of their input, so 16-bit and float
+/// images keep their precision; the dithering filters give 8-bit RGBA, as
+/// their palettes are 8-bit. Those that only work on colors keep the
+/// transparency of their input, see [`crate::alpha`].
+pub trait Filter {
+    /// Name used on the command line and in recipes.
+    fn name(&self) -> &'static str;
+
+    fn params(&self) -> &'static [ParamSpec];
+
+    /// Runs the filter with `params`, built for it by [`Params::new`].
+    fn apply(&self, img: DynamicImage, params: &Params) -> Result<DynamicImage, Error>;
+}
+
+/// Parameter values of a [`Filter`] call, with defaults filled in and kinds
+/// and ranges checked.
+pub struct Params {
+    map: HashMap<String, String>,
+}
+
+impl Params {
+    /// Collects `values` for `filter`. Names that are neither parameters of
+    /// the filter nor [`SHARED_PARAMS`] are rejected, so a misspelt key does
+    /// not silently fall back to the default.
+    pub fn new<I>(filter: &dyn Filter, values: I) -> Result<Params, Error>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let specs: Vec<&ParamSpec> = filter.params().iter().chain(SHARED_PARAMS).collect();
+        let mut map: HashMap<String, String> = HashMap::new();
+        for (key, val) in values {
+            if !specs.iter().any(|spec| spec.name == key) {
+                return Err(invalid(format!(
+                    "unknown parameter `{}` for `{}`",
+                    key,
+                    filter.name()
+                )));
+            }
+            map.insert(key, val);
+        }
+        for spec in specs {
+            match (map.get(spec.name), spec.default) {
+                (Some(val), _) => spec.check(val)?,
+                (None, Some(default)) => {
+                    map.insert(spec.name.to_string(), default.to_string());
+                }
+                (None, None) if spec.required => {
+                    return Err(invalid(format!("missing parameter `{}`", spec.name)))
+                }
+                (None, None) => {}
+            }
+        }
+        Ok(Params { map })
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.map.get(key).map(|val| val.as_str())
+    }
+
+    fn raw(&self, key: &str) -> Result<&str, Error> {
+        self.get(key)
+            .ok_or_else(|| invalid(format!("missing parameter `{}`", key)))
+    }
+
+    pub fn num<T: std::str::FromStr>(&self, key: &str) -> Result<T, Error> {
+        let val = self.raw(key)?;
+        val.parse()
+            .map_err(|_| invalid(format!("invalid value for `{}`: `{}`", key, val)))
+    }
+
+    fn pal(&self, key: &str) -> Result<Vec<[u8; 3]>, Error> {
+        let val = self.raw(key)?;
+        str_to_pal(val).map_err(|err| err.context(&format!("invalid palette for `{}`", key)))
+    }
+
+    pub fn kernel(&self, key: &str) -> Result<&'static DiffusionKernel, Error> {
+        let name = self.raw(key)?;
+        DiffusionKernel::by_name(name)
+            .ok_or_else(|| invalid(format!("unknown kernel for `{}`: `{}`", key, name)))
+    }
+
+    pub fn map(&self, key: &str) -> Result<ThresholdMap, Error> {
+        let val = self.raw(key)?;
+        let context = format!("invalid map for `{}`: `{}`", key, val);
+        if let Some(size) = val.strip_prefix("blue").and_then(|size| size.parse().ok()) {
+            return ThresholdMap::blue_noise(size, self.num("seed")?)
+                .map_err(|err| err.context(&context));
+        }
+        match ThresholdMap::by_name(val) {
+            Some(map) => Ok(map),
+            None => ThresholdMap::load_any(val).map_err(|err| err.context(&context)),
+        }
+    }
+
+    /// Palette given as hex, by name, as a palette file or extracted from
+    /// `img`, also written to the `save_pal` path if one is given.
+    pub fn palette<T: Channel>(
+        &self,
+        key: &str,
+        img: &ImageBuffer<Rgb<T>, Vec<T>>,
+    ) -> Result<Vec<[u8; 3]>, Error>
+    where
+        Rgb<T>: Pixel<Subpixel = T>,
+    {
+        let val = self.raw(key)?;
+        let pal = if PaletteFormat::from_path(val).is_some() {
+            palette_io::load(val)
+                .map_err(|err| err.context(&format!("invalid palette for `{}`: `{}`", key, val)))?
+        } else if let Some(palette) = NamedPalette::by_name(val) {
+            palette.to_vec()
+        } else if let Some((method, n)) = val.split_once(':') {
+            let n: usize = n.parse().map_err(|_| {
+                Error::InvalidPalette(format!("invalid color count for `{}`: `{}`", key, n))
+            })?;
+            let img: RgbImage = convert_depth::<T, u8>(img.clone());
+            match method {
+                "median" => quantize::median_cut(&img, n),
+                "kmeans" => quantize::kmeans(&img, n, self.num("seed")?, 32),
+                "octree" => quantize::octree(&img, n),
+                _ => {
+                    return Err(Error::InvalidPalette(format!(
+                        "unknown palette method for `{}`: `{}`",
+                        key, method
+                    )))
+                }
+            }
+        } else {
+            self.pal(key)?
+        };
+        if pal.is_empty() {
+            return Err(Error::InvalidPalette(format!(
+                "empty palette for `{}`",
+                key
+            )));
+        }
+        if let Some(path) = self.get("save_pal") {
+            palette_io::save(&pal, path)
+                .map_err(|err| err.context(&format!("cannot save palette `{}`", path)))?;
+        }
+        Ok(pal)
+    }
+
+    pub fn metric(&self, key: &str) -> Result<ColorMetric, Error> {
+        match self.get(key) {
+            Some(name) => ColorMetric::by_name(name)
+                .ok_or_else(|| invalid(format!("unknown metric for `{}`: `{}`", key, name))),
+            None => Ok(ColorMetric::default()),
+        }
+    }
+
+    /// Palette of `key` (see [`Params::palette`]) prepared for matching under
+    /// the `metric` parameter.
+    pub fn matcher<T: Channel>(
+        &self,
+        key: &str,
+        img: &ImageBuffer<Rgb<T>, Vec<T>>,
+    ) -> Result<PaletteMatcher, Error>
+    where
+        Rgb<T>: Pixel<Subpixel = T>,
+    {
+        PaletteMatcher::new(self.palette(key, img)?, self.metric("metric")?)
+            .map_err(|err| err.context(&format!("invalid palette for `{}`", key)))
+    }
+
+    pub fn color(&self, key: &str) -> Result<Rgb<u8>, Error> {
+        match self.pal(key)?.as_slice() {
+            [color] => Ok(Rgb(*color)),
+            _ => Err(invalid(format!("expected a single color for `{}`", key))),
+        }
+    }
+
+    /// Alpha handling: `preserve`, `dither` (against the `alpha_map` map) or
+    /// `matte` (onto the `matte` color).
+    pub fn alpha(&self, key: &str) -> Result<AlphaMode, Error> {
+        match self.get(key) {
+            None | Some("preserve") => Ok(AlphaMode::Preserve),
+            Some("dither") => Ok(AlphaMode::Dither(self.map("alpha_map")?)),
+            Some("matte") => Ok(AlphaMode::Matte(self.color("matte")?)),
+            Some(val) => Err(invalid(format!(
+                "unknown alpha mode for `{}`: `{}`",
+                key, val
+            ))),
+        }
+    }
+
+    pub fn curve(&self, key: &str) -> Result<Curve, Error> {
+        let val = self.raw(key)?;
+        Curve::parse(val).map_err(|err| err.context(&format!("`{}`", key)))
+    }
+
+    pub fn gradient(&self, key: &str) -> Result<Gradient, Error> {
+        let val = self.raw(key)?;
+        Gradient::parse(val)
+            .map_err(|_| invalid(format!("invalid gradient for `{}`: `{}`", key, val)))
+    }
+
+    pub fn channels(&self, key: &str) -> Result<[f32; 3], Error> {
+        let val = self.raw(key)?;
+        let channels: Option<Vec<f32>> = val.split(',').map(|v| v.trim().parse().ok()).collect();
+        match channels.as_deref() {
+            Some(&[r, g, b]) => Ok([r, g, b]),
+            _ => Err(invalid(format!("expected r,g,b for `{}`: `{}`", key, val))),
+        }
+    }
+
+    pub fn color_pairs(&self, key: &str) -> Result<Vec<Rgb<u8>>, Error> {
+        let mut colors = vec![];
+        for pair in self.raw(key)?.split(',') {
+            let (from, to) = pair
+                .split_once(':')
+                .ok_or_else(|| invalid(format!("expected from:to pairs for `{}`", key)))?;
+            for hex in [from, to] {
+                match str_to_pal(hex).ok().as_deref() {
+                    Some([color]) => colors.push(Rgb(*color)),
+                    _ => return Err(invalid(format!("invalid color for `{}`: `{}`", key, hex))),
+                }
+            }
+        }
+        Ok(colors)
+    }
+}
+
+const UNBOUNDED: ParamKind = ParamKind::Float {
+    min: f64::NEG_INFINITY,
+    max: f64::INFINITY,
+};
+const NON_NEGATIVE: ParamKind = ParamKind::Float {
+    min: 0.0,
+    max: f64::INFINITY,
+};
+const FRACTION: ParamKind = ParamKind::Float { min: 0.0, max: 1.0 };
+const FACTOR: ParamKind = ParamKind::Int {
+    min: 1,
+    max: i64::MAX,
+};
+const PIXEL_ART_FACTOR: ParamSpec = ParamSpec::required("k", ParamKind::Int { min: 2, max: 4 });
+const HUE: ParamSpec = ParamSpec::with_default(
+    "hue",
+    ParamKind::Float {
+        min: -360.0,
+        max: 360.0,
+    },
+    "0.0",
+);
+const SATURATION: ParamSpec = ParamSpec::with_default("saturation", NON_NEGATIVE, "1.0");
+const SEED: ParamSpec = ParamSpec::with_default(
+    "seed",
+    ParamKind::Int {
+        min: 0,
+        max: i64::MAX,
+    },
+    "0",
+);
+/// Parameters every filter accepts on top of its own, so a recipe can pass
+/// them to all of its steps.
+pub const SHARED_PARAMS: &[ParamSpec] = &[SEED];
+const PIXEL_SIZE: ParamSpec = ParamSpec::with_default("pixel_size", FACTOR, "1");
+const METRIC: ParamSpec = ParamSpec::with_default("metric", ParamKind::Metric, "euclidean");
+const ALPHA: ParamSpec = ParamSpec::with_default(
+    "alpha",
+    ParamKind::Choice(&["preserve", "dither", "matte"]),
+    "preserve",
+);
+const ALPHA_MAP: ParamSpec = ParamSpec::with_default("alpha_map", ParamKind::Map, "bayer8");
+const MATTE: ParamSpec = ParamSpec::with_default("matte", ParamKind::Color, "ffffff");
+const SAVE_PAL: ParamSpec = ParamSpec::optional("save_pal", ParamKind::File);
+const LINEAR: ParamSpec = ParamSpec::with_default("linear", ParamKind::Bool, "false");
+
+/// Evaluates `$body` with `$img`, a [`DynamicImage`], rebound to its RGBA
+/// buffer at its own depth: `u16` channels for 16-bit images, `f32` for
+/// float ones and `u8` otherwise. The body is expanded once per depth, so
+/// it can call the depth-generic functions directly.
+macro_rules! at_depth {
+    ($img:ident => $body:expr) => {
+        match $img {
+            DynamicImage::ImageLuma16(_)
+            | DynamicImage::ImageLumaA16(_)
+            | DynamicImage::ImageRgb16(_)
+            | DynamicImage::ImageRgba16(_) => {
+                let $img = $img.into_rgba16();
+                $body
+            }
+            DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_) => {
+                let $img = $img.into_rgba32f();
+                $body
+            }
+            _ => {
+                let $img = $img.into_rgba8();
+                $body
+            }
+        }
+    };
+}
+
+/// Runs `f` on the colors of `img`, keeping its transparency and depth.
+fn on_colors<T: Channel, F>(img: ImageBuffer<Rgba<T>, Vec<T>>, f: F) -> Result<DynamicImage, Error>
+where
+    Rgb<T>: Pixel<Subpixel = T>,
+    Rgba<T>: Pixel<Subpixel = T>,
+    F: FnOnce(ImageBuffer<Rgb<T>, Vec<T>>) -> Result<ImageBuffer<Rgb<T>, Vec<T>>, Error>,
+{
+    let rgb = f(AlphaMode::Preserve.colors(&img))?;
+    Ok(T::rgba_into_dynamic(
+        AlphaMode::Preserve.apply(&img, rgb, 1),
+    ))
+}
+
+/// [`ord_bayer_dithering`].
+pub struct Bayer;
+
+impl Filter for Bayer {
+    fn name(&self) -> &'static str {
+        "bayer"
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        const PARAMS: &[ParamSpec] = &[
+            ParamSpec::required("pal", ParamKind::Palette),
+            ParamSpec::with_default("map", ParamKind::Map, "bayer8"),
+            SEED,
+            PIXEL_SIZE,
+            ParamSpec::with_default("d", UNBOUNDED, "1.0"),
+            ParamSpec::with_default("m", UNBOUNDED, "32.0"),
+            METRIC,
+            ALPHA,
+            ALPHA_MAP,
+            MATTE,
+            SAVE_PAL,
+        ];
+        PARAMS
+    }
+
+    fn apply(&self, img: DynamicImage, params: &Params) -> Result<DynamicImage, Error> {
+        let mode = params.alpha("alpha")?;
+        let map = params.map("map")?;
+        let pixel_size: u32 = params.num("pixel_size")?;
+        let (d, m): (f32, f32) = (params.num("d")?, params.num("m")?);
+        at_depth!(img => {
+            let colors = mode.colors(&img);
+            let pal = params.matcher("pal", &colors)?;
+            let rgb = ord_bayer_dithering(colors, &pal, &map, pixel_size, d, m);
+            Ok(DynamicImage::ImageRgba8(mode.apply(&img, rgb, pixel_size)))
+        })
+    }
+}
+
+/// [`ascii_ord_bayer_dithering`], writing the text to `file`. The image
+/// passes through unchanged, like the palette written by `save_pal`.
+pub struct Ascii;
+
+impl Filter for Ascii {
+    fn name(&self) -> &'static str {
+        "ascii"
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        const PARAMS: &[ParamSpec] = &[
+            ParamSpec::required("file", ParamKind::File),
+            ParamSpec::with_default("chars", ParamKind::Text, " .:-=+*#%@"),
+            ParamSpec::with_default("map", ParamKind::Map, "bayer8"),
+            SEED,
+            ParamSpec::with_default("k", FACTOR, "1"),
+            ParamSpec::with_default(
+                "ssr",
+                ParamKind::Float {
+                    min: 0.1,
+                    max: f64::INFINITY,
+                },
+                "2.0",
+            ),
+            ParamSpec::with_default("r", UNBOUNDED, "1.0"),
+        ];
+        PARAMS
+    }
+
+    fn apply(&self, img: DynamicImage, params: &Params) -> Result<DynamicImage, Error> {
+        let chars: Vec<char> = params.raw("chars")?.chars().collect();
+        let path: &str = params.raw("file")?;
+        let file = std::fs::File::create(path)
+            .map_err(|err| Error::from(err).context(&format!("cannot write `{}`", path)))?;
+        write_ascii_ord_bayer_dithering(
+            std::io::BufWriter::new(file),
+            &img.to_rgb8(),
+            &chars,
+            &params.map("map")?,
+            params.num("k")?,
+            params.num("ssr")?,
+            params.num("r")?,
+        )?;
+        Ok(img)
+    }
+}
+
+/// [`twod_errprop_dithering`].
+pub struct ErrProp;
+
+impl Filter for ErrProp {
+    fn name(&self) -> &'static str {
+        "errprop"
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        const PARAMS: &[ParamSpec] = &[
+            ParamSpec::required("pal", ParamKind::Palette),
+            ParamSpec::with_default("kernel", ParamKind::Kernel, FLOYD_STEINBERG.name),
+            ParamSpec::with_default("serpentine", ParamKind::Bool, "false"),
+            LINEAR,
+            SEED,
+            PIXEL_SIZE,
+            ParamSpec::with_default("d", UNBOUNDED, "1.0"),
+            ParamSpec::with_default("m", UNBOUNDED, "1.0"),
+            METRIC,
+            ALPHA,
+            ALPHA_MAP,
+            MATTE,
+            SAVE_PAL,
+        ];
+        PARAMS
+    }
+
+    fn apply(&self, img: DynamicImage, params: &Params) -> Result<DynamicImage, Error> {
+        let mode = params.alpha("alpha")?;
+        let kernel = params.kernel("kernel")?;
+        let serpentine: bool = params.num("serpentine")?;
+        let linear: bool = params.num("linear")?;
+        let pixel_size: u32 = params.num("pixel_size")?;
+        let (d, m): (f32, f32) = (params.num("d")?, params.num("m")?);
+        at_depth!(img => {
+            let colors = mode.colors(&img);
+            let pal = params.matcher("pal", &colors)?;
+            let rgb = twod_errprop_dithering(
+                colors, &pal, kernel, serpentine, linear, pixel_size, d, m,
+            );
+            Ok(DynamicImage::ImageRgba8(mode.apply(&img, rgb, pixel_size)))
+        })
+    }
+}
+
+/// [`to_n_val_channels`] with `n` levels per channel.
+pub struct NVal;
+
+impl Filter for NVal {
+    fn name(&self) -> &'static str {
+        "nval"
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        const PARAMS: &[ParamSpec] = &[ParamSpec::required(
+            "n",
+            ParamKind::Int { min: 2, max: 255 },
+        )];
+        PARAMS
+    }
+
+    fn apply(&self, img: DynamicImage, params: &Params) -> Result<DynamicImage, Error> {
+        let n: u8 = params.num("n")?;
+        at_depth!(img => on_colors(img, |mut img| {
+            to_n_val_channels(&mut img, n - 1);
+            Ok(img)
+        }))
+    }
+}
+
+/// [`edit_color`].
+pub struct EditColor;
+
+impl Filter for EditColor {
+    fn name(&self) -> &'static str {
+        "editcolor"
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        const PARAMS: &[ParamSpec] = &[
+            ParamSpec::required("colors", ParamKind::ColorPairs),
+            ParamSpec::with_default("measure", NON_NEGATIVE, "0"),
+            ParamSpec::optional("metric", ParamKind::Metric),
+        ];
+        PARAMS
+    }
+
+    fn apply(&self, img: DynamicImage, params: &Params) -> Result<DynamicImage, Error> {
+        let colors = params.color_pairs("colors")?;
+        let measure: f32 = params.num("measure")?;
+        let tolerance = match params.get("metric") {
+            Some(_) => ColorTolerance::Metric(params.metric("metric")?, measure),
+            None => ColorTolerance::PerChannel(measure),
+        };
+        at_depth!(img => on_colors(img, |mut img| {
+            edit_color(&mut img, colors, tolerance);
+            Ok(img)
+        }))
+    }
+}
+
+/// [`upscale`].
+pub struct Upscale;
+
+impl Filter for Upscale {
+    fn name(&self) -> &'static str {
+        "upscale"
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        const PARAMS: &[ParamSpec] = &[ParamSpec::required("k", FACTOR)];
+        PARAMS
+    }
+
+    fn apply(&self, img: DynamicImage, params: &Params) -> Result<DynamicImage, Error> {
+        let k: u32 = params.num("k")?;
+        at_depth!(img => upscale(img, k).map(Channel::rgba_into_dynamic))
+    }
+}
+
+/// [`scale_nx`].
+pub struct ScaleNx;
+
+impl Filter for ScaleNx {
+    fn name(&self) -> &'static str {
+        "scalenx"
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        const PARAMS: &[ParamSpec] = &[PIXEL_ART_FACTOR];
+        PARAMS
+    }
+
+    fn apply(&self, img: DynamicImage, params: &Params) -> Result<DynamicImage, Error> {
+        let k: u32 = params.num("k")?;
+        at_depth!(img => scale_nx(&img, k).map(Channel::rgba_into_dynamic))
+    }
+}
+
+/// [`epx`].
+pub struct Epx;
+
+impl Filter for Epx {
+    fn name(&self) -> &'static str {
+        "epx"
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        const PARAMS: &[ParamSpec] = &[PIXEL_ART_FACTOR];
+        PARAMS
+    }
+
+    fn apply(&self, img: DynamicImage, params: &Params) -> Result<DynamicImage, Error> {
+        let k: u32 = params.num("k")?;
+        at_depth!(img => epx(&img, k).map(Channel::rgba_into_dynamic))
+    }
+}
+
+/// [`hqx`].
+pub struct Hqx;
+
+impl Filter for Hqx {
+    fn name(&self) -> &'static str {
+        "hqx"
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        const PARAMS: &[ParamSpec] = &[PIXEL_ART_FACTOR];
+        PARAMS
+    }
+
+    fn apply(&self, img: DynamicImage, params: &Params) -> Result<DynamicImage, Error> {
+        let k: u32 = params.num("k")?;
+        at_depth!(img => hqx(&img, k).map(Channel::rgba_into_dynamic))
+    }
+}
+
+/// [`xbr`].
+pub struct Xbr;
+
+impl Filter for Xbr {
+    fn name(&self) -> &'static str {
+        "xbr"
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        const PARAMS: &[ParamSpec] = &[PIXEL_ART_FACTOR];
+        PARAMS
+    }
+
+    fn apply(&self, img: DynamicImage, params: &Params) -> Result<DynamicImage, Error> {
+        let k: u32 = params.num("k")?;
+        at_depth!(img => xbr(&img, k).map(Channel::rgba_into_dynamic))
+    }
+}
+
+/// [`downscale`]. It copies pixels without averaging them, so unlike
+/// [`Resize`] it has no `linear` option.
+pub struct Downscale;
+
+impl Filter for Downscale {
+    fn name(&self) -> &'static str {
+        "downscale"
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        const PARAMS: &[ParamSpec] = &[ParamSpec::required("k", FACTOR)];
+        PARAMS
+    }
+
+    fn apply(&self, img: DynamicImage, params: &Params) -> Result<DynamicImage, Error> {
+        let k: u32 = params.num("k")?;
+        at_depth!(img => Ok(Channel::rgba_into_dynamic(downscale(img, k))))
+    }
+}
+
+/// [`downscale_grid`] along the grid [`PixelGrid::detect`] finds.
+pub struct Unscale;
+
+impl Filter for Unscale {
+    fn name(&self) -> &'static str {
+        "unscale"
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        const PARAMS: &[ParamSpec] = &[ParamSpec::with_default(
+            "pick",
+            ParamKind::Choice(&["majority", "median"]),
+            "majority",
+        )];
+        PARAMS
+    }
+
+    fn apply(&self, img: DynamicImage, params: &Params) -> Result<DynamicImage, Error> {
+        let pick = CellColor::by_name(params.raw("pick")?).unwrap_or_default();
+        at_depth!(img => {
+            let grid = PixelGrid::detect(&img);
+            Ok(Channel::rgba_into_dynamic(downscale_grid(&img, &grid, pick)))
+        })
+    }
+}
+
+/// [`resample::resize_to`], optionally in linear light; a missing side
+/// follows the aspect ratio.
+pub struct Resize;
+
+impl Filter for Resize {
+    fn name(&self) -> &'static str {
+        "resize"
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        const PARAMS: &[ParamSpec] = &[
+            ParamSpec::optional("width", FACTOR),
+            ParamSpec::optional("height", FACTOR),
+            ParamSpec::with_default(
+                "filter",
+                ParamKind::Choice(&ResampleFilter::NAMES),
+                "lanczos3",
+            ),
+            ParamSpec::with_default(
+                "fit",
+                ParamKind::Choice(&["stretch", "fit", "fill"]),
+                "stretch",
+            ),
+            LINEAR,
+        ];
+        PARAMS
+    }
+
+    fn apply(&self, img: DynamicImage, params: &Params) -> Result<DynamicImage, Error> {
+        let (x, y) = (img.width(), img.height());
+        let side = |key: &str| params.get(key).map(|_| params.num::<u32>(key)).transpose();
+        let (width, height) = match (side("width")?, side("height")?) {
+            (Some(width), Some(height)) => (width, height),
+            (Some(width), None) => (width, aspect(width, y, x)),
+            (None, Some(height)) => (aspect(height, x, y), height),
+            (None, None) => {
+                return Err(invalid("missing parameter `width` or `height`".to_string()))
+            }
+        };
+        let filter = ResampleFilter::by_name(params.raw("filter")?).unwrap_or_default();
+        let mode = FitMode::by_name(params.raw("fit")?).unwrap_or_default();
+        let linear: bool = params.num("linear")?;
+        at_depth!(img => {
+            let img = resample::resize_to(&img, width, height, filter, mode, linear);
+            Ok(Channel::rgba_into_dynamic(img))
+        })
+    }
+}
+
+/// `side` scaled by `num / den`, at least 1.
+fn aspect(side: u32, num: u32, den: u32) -> u32 {
+    ((side as f64 * num as f64 / den.max(1) as f64).round() as u32).max(1)
+}
+
+/// [`pinkize`].
+pub struct Pinkize;
+
+impl Filter for Pinkize {
+    fn name(&self) -> &'static str {
+        "pinkize"
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        const PARAMS: &[ParamSpec] = &[];
+        PARAMS
+    }
+
+    fn apply(&self, img: DynamicImage, _params: &Params) -> Result<DynamicImage, Error> {
+        at_depth!(img => on_colors(img, |mut img| {
+            pinkize(&mut img);
+            Ok(img)
+        }))
+    }
+}
+
+/// [`gradient_map`].
+pub struct GradientMap;
+
+impl Filter for GradientMap {
+    fn name(&self) -> &'static str {
+        "gradient"
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        const PARAMS: &[ParamSpec] = &[ParamSpec::required("stops", ParamKind::Gradient)];
+        PARAMS
+    }
+
+    fn apply(&self, img: DynamicImage, params: &Params) -> Result<DynamicImage, Error> {
+        let gradient: Gradient = params.gradient("stops")?;
+        at_depth!(img => on_colors(img, |mut img| {
+            gradient_map(&mut img, &gradient);
+            Ok(img)
+        }))
+    }
+}
+
+/// [`bright`], optionally in linear light.
+pub struct Bright;
+
+impl Filter for Bright {
+    fn name(&self) -> &'static str {
+        "bright"
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        const PARAMS: &[ParamSpec] = &[ParamSpec::required("c", UNBOUNDED), LINEAR];
+        PARAMS
+    }
+
+    fn apply(&self, img: DynamicImage, params: &Params) -> Result<DynamicImage, Error> {
+        let c: f32 = params.num("c")?;
+        let in_linear: bool = params.num("linear")?;
+        at_depth!(img => on_colors(img, |mut img| {
+            if in_linear {
+                return Ok(linear::in_linear(img, |mut img| {
+                    bright(&mut img, c);
+                    img
+                }));
+            }
+            bright(&mut img, c);
+            Ok(img)
+        }))
+    }
+}
+
+/// [`colorize`], optionally in linear light.
+pub struct Colorize;
+
+impl Filter for Colorize {
+    fn name(&self) -> &'static str {
+        "colorize"
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        const PARAMS: &[ParamSpec] = &[ParamSpec::required("color", ParamKind::Color), LINEAR];
+        PARAMS
+    }
+
+    fn apply(&self, img: DynamicImage, params: &Params) -> Result<DynamicImage, Error> {
+        let color: Rgb<u8> = params.color("color")?;
+        let in_linear: bool = params.num("linear")?;
+        at_depth!(img => on_colors(img, |mut img| {
+            if in_linear {
+                return Ok(linear::in_linear(img, |mut img| {
+                    colorize(&mut img, Rgb(rgb_to_linear(color.0)));
+                    img
+                }));
+            }
+            colorize(&mut img, Rgb(color.0.map(from_u8_scale)));
+            Ok(img)
+        }))
+    }
+}
+
+/// [`add`], optionally in linear light.
+pub struct Add;
+
+impl Filter for Add {
+    fn name(&self) -> &'static str {
+        "add"
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        const PARAMS: &[ParamSpec] = &[ParamSpec::required("color", ParamKind::Color), LINEAR];
+        PARAMS
+    }
+
+    fn apply(&self, img: DynamicImage, params: &Params) -> Result<DynamicImage, Error> {
+        let color: Rgb<u8> = params.color("color")?;
+        let in_linear: bool = params.num("linear")?;
+        at_depth!(img => on_colors(img, |mut img| {
+            if in_linear {
+                return Ok(linear::in_linear(img, |mut img| {
+                    add(&mut img, Rgb(rgb_to_linear(color.0)));
+                    img
+                }));
+            }
+            add(&mut img, Rgb(color.0.map(from_u8_scale)));
+            Ok(img)
+        }))
+    }
+}
+
+/// [`brightness_contrast`].
+pub struct Contrast;
+
+impl Filter for Contrast {
+    fn name(&self) -> &'static str {
+        "contrast"
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        const PARAMS: &[ParamSpec] = &[
+            ParamSpec::with_default("contrast", UNBOUNDED, "1.0"),
+            ParamSpec::with_default("brightness", UNBOUNDED, "1.0"),
+            ParamSpec::with_default(
+                "pivot",
+                ParamKind::Choice(&["mid-grey", "mean"]),
+                "mid-grey",
+            ),
+        ];
+        PARAMS
+    }
+
+    fn apply(&self, img: DynamicImage, params: &Params) -> Result<DynamicImage, Error> {
+        let contrast: f32 = params.num("contrast")?;
+        let brightness: f32 = params.num("brightness")?;
+        let pivot = params
+            .raw("pivot")
+            .map(|val| Pivot::by_name(val).unwrap_or_default())?;
+        at_depth!(img => on_colors(img, |mut img| {
+            brightness_contrast(&mut img, brightness, contrast, pivot);
+            Ok(img)
+        }))
+    }
+}
+
+/// [`levels`].
+pub struct Levels;
+
+impl Filter for Levels {
+    fn name(&self) -> &'static str {
+        "levels"
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        const PARAMS: &[ParamSpec] = &[
+            ParamSpec::with_default("black", FRACTION, "0.0"),
+            ParamSpec::with_default("white", FRACTION, "1.0"),
+            ParamSpec::with_default(
+                "gamma",
+                ParamKind::Float {
+                    min: 0.01,
+                    max: 100.0,
+                },
+                "1.0",
+            ),
+        ];
+        PARAMS
+    }
+
+    fn apply(&self, img: DynamicImage, params: &Params) -> Result<DynamicImage, Error> {
+        let black: f32 = params.num("black")?;
+        let white: f32 = params.num("white")?;
+        if white <= black {
+            return Err(invalid("`white` must be greater than `black`".to_string()));
+        }
+        let gamma: f32 = params.num("gamma")?;
+        at_depth!(img => on_colors(img, |mut img| {
+            levels(&mut img, black, white, gamma);
+            Ok(img)
+        }))
+    }
+}
+
+/// [`curves`].
+pub struct Curves;
+
+impl Filter for Curves {
+    fn name(&self) -> &'static str {
+        "curves"
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        const PARAMS: &[ParamSpec] = &[ParamSpec::required("points", ParamKind::Curve)];
+        PARAMS
+    }
+
+    fn apply(&self, img: DynamicImage, params: &Params) -> Result<DynamicImage, Error> {
+        let curve: Curve = params.curve("points")?;
+        at_depth!(img => on_colors(img, |mut img| {
+            curves(&mut img, &curve);
+            Ok(img)
+        }))
+    }
+}
+
+/// [`hsl_adjust`].
+pub struct Hsl;
+
+impl Filter for Hsl {
+    fn name(&self) -> &'static str {
+        "hsl"
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        const PARAMS: &[ParamSpec] = &[
+            HUE,
+            SATURATION,
+            ParamSpec::with_default(
+                "lightness",
+                ParamKind::Float {
+                    min: -1.0,
+                    max: 1.0,
+                },
+                "0.0",
+            ),
+        ];
+        PARAMS
+    }
+
+    fn apply(&self, img: DynamicImage, params: &Params) -> Result<DynamicImage, Error> {
+        let hue: f32 = params.num("hue")?;
+        let saturation: f32 = params.num("saturation")?;
+        let lightness: f32 = params.num("lightness")?;
+        at_depth!(img => on_colors(img, |mut img| {
+            hsl_adjust(&mut img, hue, saturation, lightness);
+            Ok(img)
+        }))
+    }
+}
+
+/// [`hsv_adjust`].
+pub struct Hsv;
+
+impl Filter for Hsv {
+    fn name(&self) -> &'static str {
+        "hsv"
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        const PARAMS: &[ParamSpec] = &[
+            HUE,
+            SATURATION,
+            ParamSpec::with_default("value", NON_NEGATIVE, "1.0"),
+        ];
+        PARAMS
+    }
+
+    fn apply(&self, img: DynamicImage, params: &Params) -> Result<DynamicImage, Error> {
+        let hue: f32 = params.num("hue")?;
+        let saturation: f32 = params.num("saturation")?;
+        let value: f32 = params.num("value")?;
+        at_depth!(img => on_colors(img, |mut img| {
+            hsv_adjust(&mut img, hue, saturation, value);
+            Ok(img)
+        }))
+    }
+}
+
+/// [`vibrance`].
+pub struct Vibrance;
+
+impl Filter for Vibrance {
+    fn name(&self) -> &'static str {
+        "vibrance"
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        const PARAMS: &[ParamSpec] = &[ParamSpec::required(
+            "amount",
+            ParamKind::Float {
+                min: -1.0,
+                max: 4.0,
+            },
+        )];
+        PARAMS
+    }
+
+    fn apply(&self, img: DynamicImage, params: &Params) -> Result<DynamicImage, Error> {
+        let amount: f32 = params.num("amount")?;
+        at_depth!(img => on_colors(img, |mut img| {
+            vibrance(&mut img, amount);
+            Ok(img)
+        }))
+    }
+}
+
+/// [`color_balance`].
+pub struct Balance;
+
+impl Filter for Balance {
+    fn name(&self) -> &'static str {
+        "balance"
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        const PARAMS: &[ParamSpec] = &[
+            ParamSpec::with_default("shadows", ParamKind::Channels, "0,0,0"),
+            ParamSpec::with_default("midtones", ParamKind::Channels, "0,0,0"),
+            ParamSpec::with_default("highlights", ParamKind::Channels, "0,0,0"),
+        ];
+        PARAMS
+    }
+
+    fn apply(&self, img: DynamicImage, params: &Params) -> Result<DynamicImage, Error> {
+        let shadows = params.channels("shadows")?;
+        let midtones = params.channels("midtones")?;
+        let highlights = params.channels("highlights")?;
+        at_depth!(img => on_colors(img, |mut img| {
+            color_balance(&mut img, shadows, midtones, highlights);
+            Ok(img)
+        }))
+    }
+}
+
+/// [`to_mc_pic`].
+pub struct Frame;
+
+impl Filter for Frame {
+    fn name(&self) -> &'static str {
+        "frame"
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        const PARAMS: &[ParamSpec] = &[
+            ParamSpec::with_default(
+                "width",
+                ParamKind::Int {
+                    min: 0,
+                    max: i64::MAX,
+                },
+                "1",
+            ),
+            ParamSpec::with_default("color", ParamKind::Color, "000000"),
+        ];
+        PARAMS
+    }
+
+    fn apply(&self, img: DynamicImage, params: &Params) -> Result<DynamicImage, Error> {
+        let width: u32 = params.num("width")?;
+        let (x, y) = (img.width(), img.height());
+        if width.checked_mul(2).is_none_or(|both| both > x.min(y)) {
+            return Err(invalid("`width` does not fit the image".to_string()));
+        }
+        let color: Rgb<u8> = params.color("color")?;
+        at_depth!(img => {
+            let mut img = img;
+            to_mc_pic(&mut img, width, Rgba(color.to_rgba().0.map(from_u8_scale)));
+            Ok(Channel::rgba_into_dynamic(img))
+        })
+    }
+}
+
+/// Every filter, in the order the command line lists them.
+pub const FILTERS: [&dyn Filter; 26] = [
+    &Bayer,
+    &Ascii,
+    &ErrProp,
+    &NVal,
+    &EditColor,
+    &Upscale,
+    &ScaleNx,
+    &Epx,
+    &Hqx,
+    &Xbr,
+    &Downscale,
+    &Unscale,
+    &Resize,
+    &Pinkize,
+    &GradientMap,
+    &Bright,
+    &Colorize,
+    &Add,
+    &Contrast,
+    &Levels,
+    &Curves,
+    &Hsl,
+    &Hsv,
+    &Vibrance,
+    &Balance,
+    &Frame,
+];
+
+pub fn by_name(name: &str) -> Option<&'static dyn Filter> {
+    FILTERS.into_iter().find(|filter| filter.name() == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbaImage;
+
+    fn values(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(key, val)| (key.to_string(), val.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn unknown_parameters_are_rejected() {
+        let err = Params::new(&Bayer, values(&[("pallete", "cga")])).err();
+        assert!(
+            matches!(err, Some(Error::InvalidParameter(message)) if message.contains("pallete"))
+        );
+        assert!(Params::new(&Bayer, values(&[("pal", "cga"), ("save_pal", "x.gpl")])).is_ok());
+        assert!(Params::new(&Bright, values(&[("save_pal", "x.gpl")])).is_err());
+    }
+
+    #[test]
+    fn shared_parameters_are_accepted_by_every_filter() {
+        for filter in FILTERS {
+            let specs = filter.params();
+            let required = specs.iter().filter(|spec| spec.required).map(|spec| {
+                let val = match spec.kind {
+                    ParamKind::Palette => "000000ffffff",
+                    ParamKind::File => "out.txt",
+                    ParamKind::ColorPairs => "000000:ffffff",
+                    ParamKind::Color => "ffffff",
+                    _ => "2",
+                };
+                (spec.name.to_string(), val.to_string())
+            });
+            let all = required.chain(values(&[("seed", "7")])).collect::<Vec<_>>();
+            let params = Params::new(filter, all).unwrap();
+            assert_eq!(params.num::<u64>("seed").unwrap(), 7, "{}", filter.name());
+        }
+    }
+
+    #[test]
+    fn oversized_maps_are_rejected_up_front() {
+        for map in ["clustered100000", "line257", "blue4096"] {
+            let err = Params::new(&Bayer, values(&[("pal", "cga"), ("map", map)])).err();
+            assert!(
+                matches!(&err, Some(Error::InvalidParameter(message)) if message.contains(map)),
+                "{:?}",
+                err
+            );
+        }
+        assert!(Params::new(&Bayer, values(&[("pal", "cga"), ("map", "clustered8")])).is_ok());
+    }
+
+    #[test]
+    fn oversized_factors_are_errors() {
+        let img = DynamicImage::ImageRgba8(RgbaImage::new(4, 3));
+        let params = Params::new(&Upscale, values(&[("k", "1073741824")])).unwrap();
+        assert!(matches!(
+            Upscale.apply(img.clone(), &params),
+            Err(Error::InvalidParameter(_))
+        ));
+        let params = Params::new(&Frame, values(&[("width", "4294967295")])).unwrap();
+        assert!(matches!(
+            Frame.apply(img, &params),
+            Err(Error::InvalidParameter(_))
+        ));
+    }
+
+    /// Grey 100 is 0.127 in linear light, so doubling it there gives 0.254,
+    /// which is 138 in sRGB rather than the 200 of doubling the sRGB value.
+    /// Likewise black and white average to 0.5 in linear light, or 188.
+    #[test]
+    fn linear_light_changes_the_arithmetic() {
+        let grey =
+            DynamicImage::ImageRgba8(RgbaImage::from_pixel(1, 1, Rgba([100, 100, 100, 255])));
+        for (linear, expected) in [("false", 200), ("true", 138)] {
+            let params = values(&[("c", "2"), ("linear", linear)]);
+            let params = Params::new(&Bright, params).unwrap();
+            let img = Bright.apply(grey.clone(), &params).unwrap().into_rgba8();
+            assert_eq!(img.get_pixel(0, 0).0, [expected, expected, expected, 255]);
+        }
+        let checkers = DynamicImage::ImageRgba8(RgbaImage::from_fn(2, 2, |x, y| {
+            let v: u8 = if (x + y) % 2 == 0 { 0 } else { 255 };
+            Rgba([v, v, v, 255])
+        }));
+        for (linear, expected) in [("false", 128), ("true", 188)] {
+            let params = values(&[("width", "1"), ("filter", "box"), ("linear", linear)]);
+            let params = Params::new(&Resize, params).unwrap();
+            let img = Resize
+                .apply(checkers.clone(), &params)
+                .unwrap()
+                .into_rgba8();
+            assert_eq!(img.get_pixel(0, 0).0, [expected, expected, expected, 255]);
+        }
+    }
+
+    #[test]
+    fn images_keep_their_depth() {
+        let deep = DynamicImage::ImageRgb16(ImageBuffer::from_pixel(2, 2, Rgb([1000, 3, 60000])));
+        let params = Params::new(&Bright, values(&[("c", "2")])).unwrap();
+        let img = Bright.apply(deep.clone(), &params).unwrap();
+        assert_eq!(img.as_rgba16().unwrap()[(1, 1)].0, [2000, 6, 65535, 65535]);
+        let params = Params::new(&Upscale, values(&[("k", "2")])).unwrap();
+        let img = Upscale.apply(deep.clone(), &params).unwrap();
+        assert_eq!(img.as_rgba16().unwrap()[(3, 3)].0, [1000, 3, 60000, 65535]);
+
+        let float = DynamicImage::ImageRgb32F(ImageBuffer::from_pixel(2, 2, Rgb([0.75, 0.1, 0.0])));
+        let params = Params::new(&Bright, values(&[("c", "2")])).unwrap();
+        let img = Bright.apply(float, &params).unwrap();
+        assert_eq!(img.as_rgba32f().unwrap()[(0, 0)].0, [1.5, 0.2, 0.0, 1.0]);
+
+        let params = Params::new(&Bayer, values(&[("pal", "000000ffffff")])).unwrap();
+        let img = Bayer.apply(deep, &params).unwrap();
+        assert!(img.as_rgba8().is_some());
+    }
+
+    #[test]
+    fn resize_takes_every_resample_filter_name() {
+        for name in ResampleFilter::NAMES {
+            let params = values(&[("width", "3"), ("filter", name)]);
+            let params = Params::new(&Resize, params).unwrap();
+            let img = DynamicImage::ImageRgba8(RgbaImage::new(6, 4));
+            let img = Resize.apply(img, &params).unwrap();
+            assert_eq!((img.width(), img.height()), (3, 2), "{}", name);
+        }
+        assert_eq!(
+            ResampleFilter::by_name("bicubic"),
+            Some(ResampleFilter::CatmullRom)
+        );
+        assert_eq!(
+            ResampleFilter::by_name("lanczos"),
+            Some(ResampleFilter::Lanczos3)
+        );
+    }
+}
@@ -0,0 +1,254 @@
+use crate::depth::Channel;
+use crate::error::Error;
+use crate::par;
+use crate::types_n_convs::str_to_pal;
+use image::{ImageBuffer, Pixel, Rgb};
+
+/// Color stops at positions in `0.0..=1.0`, interpolated linearly in sRGB.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gradient {
+    stops: Vec<(f32, [u8; 3])>,
+}
+
+impl Gradient {
+    /// Gradient through `stops`, sorted by position. Needs at least one stop;
+    /// positions before the first and after the last take its color. No
+    /// stops or a position that is not a number fail with
+    /// [`Error::InvalidParameter`].
+    pub fn new(stops: &[(f32, [u8; 3])]) -> Result<Gradient, Error> {
+        let mut stops: Vec<(f32, [u8; 3])> = stops.to_vec();
+        if stops.is_empty() {
+            return Err(Error::InvalidParameter(
+                "a gradient needs at least one stop".to_string(),
+            ));
+        }
+        if stops.iter().any(|stop| stop.0.is_nan()) {
+            return Err(Error::InvalidParameter(
+                "gradient stop positions must be numbers".to_string(),
+            ));
+        }
+        stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Ok(Gradient { stops })
+    }
+
+    /// `colors` spread evenly from 0 to 1, failing like [`Gradient::new`].
+    pub fn even(colors: &[[u8; 3]]) -> Result<Gradient, Error> {
+        let last: f32 = colors.len().saturating_sub(1).max(1) as f32;
+        let stops: Vec<(f32, [u8; 3])> = colors
+            .iter()
+            .enumerate()
+            .map(|(i, color)| (i as f32 / last, *color))
+            .collect();
+        Gradient::new(&stops)
+    }
+
+    /// Shadows to `dark`, highlights to `light`.
+    pub fn duotone(dark: [u8; 3], light: [u8; 3]) -> Gradient {
+        Gradient {
+            stops: vec![(0.0, dark), (1.0, light)],
+        }
+    }
+
+    /// Like [`Gradient::duotone`] with `mid` for the midtones.
+    pub fn tritone(dark: [u8; 3], mid: [u8; 3], light: [u8; 3]) -> Gradient {
+        Gradient {
+            stops: vec![(0.0, dark), (0.5, mid), (1.0, light)],
+        }
+    }
+
+    /// Parses a preset name (see [`NamedGradient::by_name`]) or hex colors
+    /// separated by commas, spread evenly unless given as `position:hex`,
+    /// e.g. `000000,ff00cc,ffffcc` or `0:000000,0.3:ff00cc,1:ffffcc`.
+    pub fn parse(text: &str) -> Result<Gradient, Error> {
+        if let Some(preset) = NamedGradient::by_name(text) {
+            return Ok(preset.to_gradient());
+        }
+        let invalid = || Error::InvalidParameter(format!("invalid gradient: `{}`", text));
+        let parse_color = |hex: &str| match str_to_pal(hex).ok()?.as_slice() {
+            [color] => Some(*color),
+            _ => None,
+        };
+        let stops: Vec<&str> = text.split(',').map(str::trim).collect();
+        if stops.iter().all(|stop| !stop.contains(':')) {
+            let colors: Option<Vec<[u8; 3]>> = stops.into_iter().map(parse_color).collect();
+            return Gradient::even(&colors.ok_or_else(invalid)?).map_err(|_| invalid());
+        }
+        let stops: Option<Vec<(f32, [u8; 3])>> = stops
+            .into_iter()
+            .map(|stop| {
+                let (pos, hex) = stop.split_once(':')?;
+                Some((pos.trim().parse().ok()?, parse_color(hex.trim())?))
+            })
+            .collect();
+        Gradient::new(&stops.ok_or_else(invalid)?).map_err(|_| invalid())
+    }
+
+    /// The color at position `t`, channels in `0.0..=1.0`.
+    pub fn at(&self, t: f32) -> [f32; 3] {
+        let i: usize = self.stops.partition_point(|stop| stop.0 <= t);
+        let (a, b) = match i {
+            0 => (self.stops[0], self.stops[0]),
+            i if i == self.stops.len() => (self.stops[i - 1], self.stops[i - 1]),
+            i => (self.stops[i - 1], self.stops[i]),
+        };
+        let f: f32 = if b.0 > a.0 {
+            (t - a.0) / (b.0 - a.0)
+        } else {
+            0.0
+        };
+        [0, 1, 2].map(|c| (a.1[c] as f32 + (b.1[c] as f32 - a.1[c] as f32) * f) / 255.0)
+    }
+}
+
+/// A gradient preset, picked by name.
+pub struct NamedGradient {
+    pub name: &'static str,
+    pub stops: &'static [(f32, [u8; 3])],
+}
+
+/// Black through hot pink to pale yellow by luma. Not a preset for
+/// [`crate::pinkize`], which works on the channels of each pixel and gives
+/// different colors; the two are separate looks.
+pub const PINK: NamedGradient = NamedGradient {
+    name: "pink",
+    stops: &[
+        (0.0, [0x00, 0x00, 0x00]),
+        (0.5, [0xff, 0x00, 0xcc]),
+        (1.0, [0xff, 0xff, 0xcc]),
+    ],
+};
+
+pub const GRAYSCALE: NamedGradient = NamedGradient {
+    name: "grayscale",
+    stops: &[(0.0, [0x00, 0x00, 0x00]), (1.0, [0xff, 0xff, 0xff])],
+};
+
+/// Duotone of dark brown and cream.
+pub const SEPIA: NamedGradient = NamedGradient {
+    name: "sepia",
+    stops: &[(0.0, [0x2b, 0x1a, 0x0e]), (1.0, [0xf5, 0xe6, 0xc8])],
+};
+
+/// Duotone of Prussian blue and paper white.
+pub const CYANOTYPE: NamedGradient = NamedGradient {
+    name: "cyanotype",
+    stops: &[(0.0, [0x0b, 0x2a, 0x4a]), (1.0, [0xe8, 0xf1, 0xf5])],
+};
+
+/// Tritone of black, deep red and warm yellow.
+pub const FIRE: NamedGradient = NamedGradient {
+    name: "fire",
+    stops: &[
+        (0.0, [0x00, 0x00, 0x00]),
+        (0.5, [0xcc, 0x22, 0x00]),
+        (1.0, [0xff, 0xee, 0x66]),
+    ],
+};
+
+/// Tritone of navy, teal and mint.
+pub const OCEAN: NamedGradient = NamedGradient {
+    name: "ocean",
+    stops: &[
+        (0.0, [0x0a, 0x0f, 0x3c]),
+        (0.5, [0x00, 0x80, 0x80]),
+        (1.0, [0xc8, 0xff, 0xe6]),
+    ],
+};
+
+pub const GRADIENTS: [&NamedGradient; 6] = [&PINK, &GRAYSCALE, &SEPIA, &CYANOTYPE, &FIRE, &OCEAN];
+
+impl NamedGradient {
+    /// Looks a preset up by name, ignoring case.
+    pub fn by_name(name: &str) -> Option<&'static NamedGradient> {
+        GRADIENTS
+            .into_iter()
+            .find(|gradient| gradient.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn to_gradient(&self) -> Gradient {
+        Gradient {
+            stops: self.stops.to_vec(),
+        }
+    }
+}
+
+/// Replaces every pixel with the color of `gradient` at its Rec. 709 luma,
+/// so shadows take the first stops and highlights the last.
+pub fn gradient_map<T: Channel>(img: &mut ImageBuffer<Rgb<T>, Vec<T>>, gradient: &Gradient)
+where
+    Rgb<T>: Pixel<Subpixel = T>,
+{
+    par::for_each_pixel(img, |pixel| {
+        let [r, g, b] = pixel.0.map(|v| v.value() / T::MAX);
+        let luma: f32 = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+        pixel.0 = gradient.at(luma).map(|v| T::from_value_rounded(v * T::MAX));
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stops(gradient: &Gradient) -> Vec<(f32, [u8; 3])> {
+        gradient.stops.clone()
+    }
+
+    #[test]
+    fn parse_reads_presets_and_stops() {
+        assert_eq!(Gradient::parse("Fire").unwrap(), FIRE.to_gradient());
+        let even = Gradient::parse("000000, ff00cc,ffffcc").unwrap();
+        assert_eq!(
+            stops(&even),
+            [
+                (0.0, [0, 0, 0]),
+                (0.5, [255, 0, 204]),
+                (1.0, [255, 255, 204])
+            ]
+        );
+        let placed = Gradient::parse("1:ffffcc,0:000000, 0.3 : ff00cc").unwrap();
+        assert_eq!(
+            stops(&placed),
+            [
+                (0.0, [0, 0, 0]),
+                (0.3, [255, 0, 204]),
+                (1.0, [255, 255, 204])
+            ]
+        );
+        assert_eq!(
+            stops(&Gradient::parse("123456").unwrap()),
+            [(0.0, [18, 52, 86])]
+        );
+    }
+
+    #[test]
+    fn parse_rejects_malformed_stops() {
+        for text in [
+            "",
+            "nope",
+            "000000,gggggg",
+            "000000,1:ffffff",
+            "x:000000",
+            "nan:000000",
+            "0:000000,1:",
+            "0:000000ffffff",
+        ] {
+            assert!(
+                matches!(Gradient::parse(text), Err(Error::InvalidParameter(_))),
+                "`{}` was accepted",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn at_clamps_to_the_ends_and_interpolates_between() {
+        let gradient = Gradient::parse("0.25:000000,0.75:ff8000").unwrap();
+        assert_eq!(gradient.at(0.0), [0.0, 0.0, 0.0]);
+        assert_eq!(gradient.at(0.25), [0.0, 0.0, 0.0]);
+        assert_eq!(gradient.at(0.5), [0.5, 64.0 / 255.0, 0.0]);
+        assert_eq!(gradient.at(0.75), [1.0, 128.0 / 255.0, 0.0]);
+        assert_eq!(gradient.at(1.0), [1.0, 128.0 / 255.0, 0.0]);
+        let single = Gradient::parse("123456").unwrap();
+        assert_eq!(single.at(0.7), [18.0 / 255.0, 52.0 / 255.0, 86.0 / 255.0]);
+    }
+}
@@ -1,13 +1,18 @@
+use crate::color_dist::ColorMetric;
+use crate::depth::{from_u8_scale, to_u8_scale, Channel};
+use crate::diffusion::DiffusionKernel;
+use crate::error::Error;
+use crate::nearest::PaletteMatcher;
+use crate::par;
+use crate::threshold::ThresholdMap;
 use crate::types_n_convs::*;
-pub use image::{DynamicImage, ImageBuffer, Rgb, RgbImage};
-pub use std::fs::File;
-pub use std::io::Read;
-use std::io::{Error, Write};
+use image::{ColorType, DynamicImage, ImageBuffer, ImageFormat, Pixel, Rgb, RgbImage, Rgba};
+use std::collections::HashSet;
+use std::io::Write;
 use std::path::Path;
-pub use std::str;
-pub use std::{cmp, vec};
-pub use Vec;
 
+/// Classic 8x8 Bayer threshold matrix with values in `0..64`; convert it
+/// with `ThresholdMap::from` to dither with it.
 pub const BAYER_8X8: [[u8; 8]; 8] = [
     [0, 32, 8, 40, 2, 34, 10, 42],
     [48, 16, 56, 24, 50, 18, 58, 26],
@@ -19,52 +24,6 @@ pub const BAYER_8X8: [[u8; 8]; 8] = [
     [63, 31, 55, 23, 61, 29, 53, 21],
 ];
 
-fn rgb_dist(one: [u8; 3], two: [u8; 3]) -> u32 {
-    ((i32_sub(one[0], two[0]).pow(2)
-        + i32_sub(one[1], two[1]).pow(2)
-        + i32_sub(one[2], two[2]).pow(2)) as f32)
-        .sqrt() as u32
-}
-
-fn closest_color(colors_from: Vec<[u8; 3]>, color_to: [u8; 3]) -> Rgb<u8> {
-    let mut mindist: u32 = 65535;
-    let mut dist: u32;
-    let mut color_index: usize = 0;
-    let mut color: [u8; 3];
-    for ci in 0..colors_from.len() {
-        color = colors_from[ci];
-        dist = rgb_dist(color, color_to);
-        if dist < mindist {
-            mindist = dist;
-            color_index = ci;
-        }
-    }
-    let res: [u8; 3] = colors_from[color_index];
-    Rgb([
-        if res[0] < 0 {
-            0
-        } else if res[0] > 255 {
-            255
-        } else {
-            res[0] as u8
-        },
-        if res[1] < 0 {
-            0
-        } else if res[1] > 255 {
-            255
-        } else {
-            res[1] as u8
-        },
-        if res[2] < 0 {
-            0
-        } else if res[2] > 255 {
-            255
-        } else {
-            res[2] as u8
-        },
-    ])
-}
-
 fn closest_index(max_index: usize, val_to: f32) -> usize {
     let mut mindist: f32 = 65535.0;
     let mut dist: f32;
@@ -76,284 +35,481 @@ fn closest_index(max_index: usize, val_to: f32) -> usize {
             index = i;
         }
     }
-    return index;
+    index
 }
 
-fn measure_equal(one: Rgb<u8>, two: Rgb<u8>, measure: i32) -> bool {
-    for i in 0..3 {
-        if (two[i as usize] as i32 - one[i as usize] as i32).abs() > measure {
-            return false;
-        }
-    }
-    return true;
-}
-
-pub fn ord_bayer_dithering(
-    img: ImageBuffer<Rgb<u8>, Vec<u8>>,
-    pal: Vec<[u8; 3]>,
-    mat: [[u8; 8]; 8],
+/// Ordered dithering against the palette of `pal` using the threshold map
+/// `map`.
+///
+/// Every `pixel_size`-th pixel is sampled, divided by `d` and offset by `m`
+/// times its normalised threshold before being snapped to the palette, so
+/// the result is `pixel_size` times smaller than `img`.
+pub fn ord_bayer_dithering<T: Channel>(
+    img: ImageBuffer<Rgb<T>, Vec<T>>,
+    pal: &PaletteMatcher,
+    map: &ThresholdMap,
     pixel_size: u32,
     d: f32,
     m: f32,
-) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+) -> ImageBuffer<Rgb<u8>, Vec<u8>>
+where
+    Rgb<T>: Pixel<Subpixel = T>,
+{
     let (x, y) = img.dimensions();
     let mut nimg: ImageBuffer<Rgb<u8>, Vec<u8>> =
-        RgbImage::new(x / pixel_size + 1, y / pixel_size + 1);
-    let mut ic = 0;
-    for i in (0..y).step_by(pixel_size as usize) {
-        let mut jc = 0;
-        for j in (0..x).step_by(pixel_size as usize) {
-            let val: f32 =
-                mat[(ic as f32 % 8.0) as usize][(jc as f32 % 8.0) as usize] as f32 / 64.0;
-            let color: Rgb<u8> = closest_color(
-                pal.clone(),
-                rgb_to_arr(rgb_add(
-                    rgb_div(*img.get_pixel(j, i), d),
-                    u8_to_rgb((m * val) as u8),
-                )),
-            );
+        RgbImage::new(x.div_ceil(pixel_size), y.div_ceil(pixel_size));
+    for (ic, i) in (0_u32..).zip((0..y).step_by(pixel_size as usize)) {
+        for (jc, j) in (0_u32..).zip((0..x).step_by(pixel_size as usize)) {
+            let val: f32 = map.value(jc, ic);
+            let offset: f32 = (m * val) as u8 as f32;
+            let pixel: Rgb<T> = *img.get_pixel(j, i);
+            let color: Rgb<u8> =
+                Rgb(pal.nearest(pixel.0.map(|v| f32::to_u8(to_u8_scale(v) / d + offset))));
             nimg.put_pixel(jc, ic, color);
-            jc += 1;
         }
-        ic += 1;
     }
     nimg
 }
 
-pub fn twod_errprop_dithering(
-    img: ImageBuffer<Rgb<u8>, Vec<u8>>,
-    pal: Vec<[i32; 3]>,
+/// Error diffusion dithering against the palette of `pal`, spreading each pixel's
+/// quantization error to its neighbours according to `kernel`.
+///
+/// Every `pixel_size`-th pixel is sampled like in [`ord_bayer_dithering`].
+/// The input is divided by `d` and the carried error is scaled by `m`;
+/// `d = 1.0` and `m = 1.0` give the reference algorithm of the kernel.
+///
+/// With `serpentine` set, odd rows are scanned right to left with the kernel
+/// mirrored, which breaks up the directional artifacts of raster scanning.
+///
+/// With `linear` set, the error is measured and spread in linear light
+/// rather than on the sRGB values, so dithered gradients keep the
+/// brightness of the source. Palette matching still happens in sRGB under
+/// the metric of `pal`.
+#[allow(clippy::too_many_arguments)]
+pub fn twod_errprop_dithering<T: Channel>(
+    img: ImageBuffer<Rgb<T>, Vec<T>>,
+    pal: &PaletteMatcher,
+    kernel: &DiffusionKernel,
+    serpentine: bool,
+    linear: bool,
     pixel_size: u32,
     d: f32,
     m: f32,
-) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+) -> ImageBuffer<Rgb<u8>, Vec<u8>>
+where
+    Rgb<T>: Pixel<Subpixel = T>,
+{
     let (x, y) = img.dimensions();
-    let mut nimg: ImageBuffer<Rgb<u8>, Vec<u8>> =
-        RgbImage::new(x / pixel_size + 1, y / pixel_size + 1);
-    let mut ic = 0;
-    let mut jc;
-    let mut err_line: Vec<[i32; 3]> = vec![[0; 3]; (x / pixel_size) as usize];
-    let mut curr_pixel: Rgb<u8>;
-    let mut upper_pixel: Rgb<u8>;
-    let mut color: Rgb<u8>;
-    for i in (1..y).step_by(pixel_size as usize) {
-        let mut err = [0; 3];
-        jc = 0;
-        for j in (0..x).step_by(pixel_size as usize) {
-            curr_pixel = *img.get_pixel(j, i);
-            upper_pixel = *img.get_pixel(j, i - 1);
-            color = closest_color(
-                pal.clone(),
-                [
-                    (curr_pixel[0] as f32 / d) as i32
-                        + ((err[0] + err_line[0][0]) as f32 * m) as i32,
-                    (curr_pixel[1] as f32 / d) as i32
-                        + ((err[1] + err_line[0][1]) as f32 * m) as i32,
-                    (curr_pixel[2] as f32 / d) as i32
-                        + ((err[2] + err_line[0][2]) as f32 * m) as i32,
-                ],
-            );
-            nimg.put_pixel(jc, ic, color);
-            err = [
-                curr_pixel[0] as i32 - color[0] as i32,
-                curr_pixel[1] as i32 - color[1] as i32,
-                curr_pixel[2] as i32 - color[2] as i32,
-            ];
-            err_line[jc as usize] = [
-                upper_pixel[0] as i32 - color[0] as i32,
-                upper_pixel[1] as i32 - color[1] as i32,
-                upper_pixel[2] as i32 - color[2] as i32,
+    let (w, h) = (x.div_ceil(pixel_size), y.div_ceil(pixel_size));
+    let mut nimg: ImageBuffer<Rgb<u8>, Vec<u8>> = RgbImage::new(w, h);
+    let mut errs: Vec<[f32; 3]> = vec![[0.0; 3]; (w * h) as usize];
+    // Values are on the 0..255 scale, in linear light if `linear` is set.
+    let decode = |v: f32| {
+        if linear {
+            srgb_to_linear(v / 255.0) * 255.0
+        } else {
+            v
+        }
+    };
+    let encode = |v: f32| {
+        if linear {
+            linear_to_srgb((v / 255.0).clamp(0.0, 1.0)) * 255.0
+        } else {
+            v
+        }
+    };
+    let pal_values: Vec<[f32; 3]> = pal
+        .palette()
+        .iter()
+        .map(|color| color.map(|v| decode(v as f32)))
+        .collect();
+    for ic in 0..h {
+        let dir: i32 = if serpentine && ic % 2 == 1 { -1 } else { 1 };
+        for n in 0..w {
+            let jc: u32 = if dir < 0 { w - 1 - n } else { n };
+            let pixel: Rgb<T> = *img.get_pixel(jc * pixel_size, ic * pixel_size);
+            let err: [f32; 3] = errs[(ic * w + jc) as usize];
+            let target: [f32; 3] = [
+                decode(to_u8_scale(pixel[0])) / d + err[0] * m,
+                decode(to_u8_scale(pixel[1])) / d + err[1] * m,
+                decode(to_u8_scale(pixel[2])) / d + err[2] * m,
             ];
-            jc += 1;
+            let index: usize = pal.nearest_index(target.map(|v| f32::to_u8(encode(v).round())));
+            nimg.put_pixel(jc, ic, Rgb(pal.palette()[index]));
+            for &(dx, dy, weight) in kernel.weights {
+                let (nj, ni) = (jc as i32 + dx * dir, ic + dy);
+                if nj < 0 || nj >= w as i32 || ni >= h {
+                    continue;
+                }
+                let share: f32 = weight / kernel.divisor;
+                let nerr = &mut errs[(ni * w + nj as u32) as usize];
+                for c in 0..3 {
+                    nerr[c] += (target[c] - pal_values[index][c]) * share;
+                }
+            }
         }
-        ic += 1;
     }
     nimg
 }
 
+/// Renders `img` as characters from `pal` (darkest first), one line of text
+/// per row of characters, each ending in a newline.
+///
+/// Every character stands for a cell `k` pixels wide and `k * ssr` pixels
+/// tall and takes the brightness of its top left pixel, dithered with `map`
+/// laid over the grid of characters; `r` scales the threshold map offset.
+/// `k` is the sampling step, at least 1. `ssr` corrects for the aspect of
+/// the font: the height of a character over its width, about 2 for most
+/// terminal fonts, so the text keeps the proportions of the image. With 1
+/// the cells are square.
 pub fn ascii_ord_bayer_dithering(
-    img: &mut ImageBuffer<Rgb<u8>, Vec<u8>>,
-    pal: Vec<char>,
-    mat: [[u8; 8]; 8],
-    k: f32,
+    img: &ImageBuffer<Rgb<u8>, Vec<u8>>,
+    pal: &[char],
+    map: &ThresholdMap,
+    k: u32,
     ssr: f32,
     r: f32,
-) -> Result<(), Error> {
-    let mut output = File::create("ascii.txt")?;
-    let (x, y) = img.dimensions();
-    let pal_size: usize = pal.len();
-    let kh: f32 = k * ssr;
-    for i in 0..y {
-        if (i as f32 % kh) as i32 == 0 {
-            for j in 0..x {
-                if (j as f32 % k) as i32 == 0 {
-                    let pixel: Rgb<u8> = *img.get_pixel(j, i);
-                    let brightness: f32 =
-                        (pixel[0] as f32 + pixel[1] as f32 + pixel[2] as f32) / 255.0 / 3.0;
-                    let val: f32 = mat[((i / k as u32) as f32 % 8.0) as usize]
-                        [((j / k as u32) as f32 % 8.0) as usize]
-                        as f32
-                        / 64.0;
-                    let c_index = closest_index(pal_size, brightness * pal_size as f32 + r * val);
-                    write!(output, "{}", pal[c_index])?;
-                }
-            }
-        }
+) -> Result<String, Error> {
+    if pal.is_empty() {
+        return Err(Error::InvalidPalette("empty character palette".to_string()));
     }
+    let (x, y) = img.dimensions();
+    let k: u32 = k.max(1);
+    let kh: f32 = (k as f32 * ssr).max(1.0);
+    let (columns, rows) = (x.div_ceil(k), (y as f32 / kh).ceil() as u32);
+    let lines: Vec<String> = par::rows(rows, |row| {
+        let i: u32 = (row as f32 * kh) as u32;
+        let mut line: String = (0..columns)
+            .map(|column| {
+                let pixel: Rgb<u8> = *img.get_pixel(column * k, i);
+                let brightness: f32 =
+                    (pixel[0] as f32 + pixel[1] as f32 + pixel[2] as f32) / 255.0 / 3.0;
+                let val: f32 = map.value(column, row);
+                pal[closest_index(pal.len(), brightness * pal.len() as f32 + r * val)]
+            })
+            .collect();
+        line.push('\n');
+        line
+    });
+    Ok(lines.concat())
+}
+
+/// Writes the text of [`ascii_ord_bayer_dithering`] to `writer`, e.g. a
+/// file or standard output.
+pub fn write_ascii_ord_bayer_dithering<W: Write>(
+    mut writer: W,
+    img: &ImageBuffer<Rgb<u8>, Vec<u8>>,
+    pal: &[char],
+    map: &ThresholdMap,
+    k: u32,
+    ssr: f32,
+    r: f32,
+) -> Result<(), Error> {
+    let text: String = ascii_ord_bayer_dithering(img, pal, map, k, ssr, r)?;
+    writer.write_all(text.as_bytes())?;
     Ok(())
 }
 
-pub fn to_colors(img: &mut ImageBuffer<Rgb<u8>, Vec<u8>>) -> Vec<[i32; 3]> {
-    let (x, y) = img.dimensions();
-    let mut colors: Vec<[i32; 3]> = vec![];
-    let mut pixel: [u8; 3];
-    let mut color: [i32; 3];
-    for i in 0..y {
-        for j in 0..x {
-            pixel = img.get_pixel(j, i).0;
-            color = [pixel[0] as i32, pixel[1] as i32, pixel[2] as i32];
-            if !(colors.contains(&color)) {
-                colors.push(color);
-            }
-        }
+/// Lists every distinct color of `img` in scan order.
+pub fn to_colors(img: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> Vec<[u8; 3]> {
+    let mut seen: HashSet<[u8; 3]> = HashSet::new();
+    img.pixels()
+        .map(|pixel| pixel.0)
+        .filter(|color| seen.insert(*color))
+        .collect()
+}
+
+/// Posterizes each channel to multiples of `255 / n`.
+pub fn to_n_val_channels<T: Channel>(img: &mut ImageBuffer<Rgb<T>, Vec<T>>, n: u8)
+where
+    Rgb<T>: Pixel<Subpixel = T>,
+{
+    let step: f32 = T::from_value(T::MAX / n as f32).value();
+    par::for_each_pixel(img, |pixel| {
+        pixel.0 = pixel
+            .0
+            .map(|v| T::from_value((v.value() / step).trunc() * step))
+    });
+}
+
+/// How close a pixel has to be to a color for [`edit_color`] to replace it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorTolerance {
+    /// No channel differs by more than this many levels.
+    PerChannel(f32),
+    /// Within this distance under the metric, see [`ColorMetric::dist`].
+    Metric(ColorMetric, f32),
+}
+
+impl Default for ColorTolerance {
+    /// Exact matches only.
+    fn default() -> Self {
+        ColorTolerance::PerChannel(0.0)
     }
-    colors
 }
 
-pub fn to_n_val_channels(img: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, mut n: u8) {
-    let (x, y) = img.dimensions();
-    let mut pixel_val: [u8; 3];
-    n = 255 / n;
-    for i in 0..y {
-        for j in 0..x {
-            pixel_val = img.get_pixel(j, i).0;
-            img.put_pixel(
-                j,
-                i,
-                Rgb([
-                    (pixel_val[0] as f32 / n as f32) as u8 * n,
-                    (pixel_val[1] as f32 / n as f32) as u8 * n,
-                    (pixel_val[2] as f32 / n as f32) as u8 * n,
-                ]),
-            )
+impl ColorTolerance {
+    pub fn matches(&self, a: [u8; 3], b: [u8; 3]) -> bool {
+        match *self {
+            ColorTolerance::PerChannel(measure) => {
+                (0..3).all(|c| (a[c] as f32 - b[c] as f32).abs() <= measure)
+            }
+            ColorTolerance::Metric(metric, measure) => metric.dist(a, b) <= measure,
         }
     }
 }
 
-pub fn edit_color(img: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, colors: Vec<Rgb<u8>>, measure: i32) {
-    let (x, y) = img.dimensions();
-    for i in 0..y {
-        for j in 0..x {
-            for k in (0..colors.len()).step_by(2) {
-                if measure_equal(colors[k], *img.get_pixel(j, i), measure) {
-                    img.put_pixel(j, i, colors[k + 1]);
-                }
+/// Replaces colors pairwise: every pixel within `tolerance` of
+/// `colors[2 * i]` becomes `colors[2 * i + 1]`. Pixels of 16-bit and float
+/// images are compared as their nearest 8-bit color.
+pub fn edit_color<T: Channel>(
+    img: &mut ImageBuffer<Rgb<T>, Vec<T>>,
+    colors: Vec<Rgb<u8>>,
+    tolerance: ColorTolerance,
+) where
+    Rgb<T>: Pixel<Subpixel = T>,
+{
+    par::for_each_pixel(img, |pixel| {
+        for k in (0..colors.len()).step_by(2) {
+            let color: [u8; 3] = pixel.0.map(|v| f32::to_u8(to_u8_scale(v).round()));
+            if tolerance.matches(colors[k].0, color) {
+                *pixel = Rgb(colors[k + 1].0.map(from_u8_scale));
             }
         }
-    }
+    });
 }
 
-pub fn upscale(img: ImageBuffer<Rgb<u8>, Vec<u8>>, k: u32) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+/// Nearest-neighbour enlargement by the integer factor `k`, at least 1.
+///
+/// An enlarged size past `u32::MAX` is an [`Error::InvalidParameter`].
+pub fn upscale<P: Pixel + Send + Sync>(
+    img: ImageBuffer<P, Vec<P::Subpixel>>,
+    k: u32,
+) -> Result<ImageBuffer<P, Vec<P::Subpixel>>, Error>
+where
+    P::Subpixel: Send + Sync,
+{
     let (x, y) = img.dimensions();
-    let mut nimg: ImageBuffer<Rgb<u8>, Vec<u8>> = RgbImage::new(x * k, y * k);
-    for i in 0..(y * k) {
-        for j in 0..(x * k) {
-            nimg.put_pixel(j, i, *img.get_pixel(j / k, i / k));
-        }
+    let k: u32 = k.max(1);
+    match (x.checked_mul(k), y.checked_mul(k)) {
+        (Some(width), Some(height)) => Ok(par::from_fn(width, height, |j, i| {
+            *img.get_pixel(j / k, i / k)
+        })),
+        _ => Err(Error::InvalidParameter(format!(
+            "cannot scale {}x{} by {}, the result is too large",
+            x, y, k
+        ))),
     }
-    nimg
 }
 
-pub fn downscale(img: ImageBuffer<Rgb<u8>, Vec<u8>>, k: u32) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+/// Point-sampled reduction keeping every `k`-th pixel, starting with the
+/// first. See [`crate::resample::resize`] for filtered resizing.
+pub fn downscale<P: Pixel>(
+    img: ImageBuffer<P, Vec<P::Subpixel>>,
+    k: u32,
+) -> ImageBuffer<P, Vec<P::Subpixel>> {
     let (x, y) = img.dimensions();
-    let mut nimg: ImageBuffer<Rgb<u8>, Vec<u8>> = RgbImage::new(x / k + 1, y / k + 1);
-    let mut ic: u32 = 0;
-    for i in (0..y).step_by(k as usize) {
-        let mut jc: u32 = 0;
-        for j in (0..x).step_by(k as usize) {
+    let mut nimg: ImageBuffer<P, Vec<P::Subpixel>> = ImageBuffer::new(x.div_ceil(k), y.div_ceil(k));
+    for (ic, i) in (0_u32..).zip((0..y).step_by(k as usize)) {
+        for (jc, j) in (0_u32..).zip((0..x).step_by(k as usize)) {
             nimg.put_pixel(jc, ic, *img.get_pixel(j, i));
-            jc += 1;
         }
-        ic += 1;
     }
     nimg
 }
 
-pub fn pinkize(img: &mut ImageBuffer<Rgb<u8>, Vec<u8>>) {
-    let (x, y) = img.dimensions();
-    for i in 0..y {
-        for j in 0..x {
-            let pixel: Rgb<u8> = *img.get_pixel(j, i);
-            let c_max: u8 = cmp::max(pixel[2], cmp::max(pixel[0], pixel[1]));
-            let c_min: u8 = cmp::min(pixel[2], cmp::min(pixel[0], pixel[1]));
-            img.put_pixel(j, i, Rgb([c_max, c_min, (c_max as f32 * 0.8) as u8]));
-        }
-    }
+/// Tints the image pink: red takes the strongest channel of each pixel,
+/// green the weakest and blue 0.8 of the strongest, so greys turn warm and
+/// saturated colors hot pink. The `pink` gradient preset,
+/// [`crate::gradient::PINK`], is a separate luma-based look.
+pub fn pinkize<T: Channel>(img: &mut ImageBuffer<Rgb<T>, Vec<T>>)
+where
+    Rgb<T>: Pixel<Subpixel = T>,
+{
+    par::for_each_pixel(img, |pixel| {
+        let [r, g, b] = pixel.0.map(|v| v.value());
+        let (c_max, c_min) = (r.max(g).max(b), r.min(g).min(b));
+        pixel.0 = [c_max, c_min, c_max * 0.8].map(T::from_value);
+    });
 }
 
-pub fn bright(img: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, c: f32) {
-    let (x, y) = img.dimensions();
-    for i in 0..y {
-        for j in 0..x {
-            let pixel: Rgb<u8> = *img.get_pixel(j, i);
-            img.put_pixel(
-                j,
-                i,
-                Rgb([
-                    u8_mul(pixel[0], c),
-                    u8_mul(pixel[1], c),
-                    u8_mul(pixel[2], c),
-                ]),
-            );
-        }
-    }
+/// Multiplies every channel by `c`, saturating at 255.
+pub fn bright<T: Channel>(img: &mut ImageBuffer<Rgb<T>, Vec<T>>, c: f32)
+where
+    Rgb<T>: Pixel<Subpixel = T>,
+{
+    par::for_each_pixel(img, |pixel| {
+        pixel.0 = pixel.0.map(|v| T::from_value(v.value() * c))
+    });
 }
 
-pub fn colorize(img: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, color: Rgb<u8>) {
-    let (x, y) = img.dimensions();
-    for i in 0..y {
-        for j in 0..x {
-            let pixel: Rgb<u8> = *img.get_pixel(j, i);
-            let brightness: f32 = (pixel[0] as f32 / 255.0
-                + pixel[1] as f32 / 255.0
-                + pixel[2] as f32 / 255.0) as f32;
-            img.put_pixel(j, i, rgb_mul(color, brightness));
-        }
-    }
+/// Tints the image with `color` scaled by each pixel's brightness.
+pub fn colorize<T: Channel>(img: &mut ImageBuffer<Rgb<T>, Vec<T>>, color: Rgb<T>)
+where
+    Rgb<T>: Pixel<Subpixel = T>,
+{
+    par::for_each_pixel(img, |pixel| {
+        let [r, g, b] = pixel.0.map(T::value);
+        let brightness: f32 = r / T::MAX + g / T::MAX + b / T::MAX;
+        *pixel = Rgb(color.0.map(|v| T::from_value(v.value() * brightness)));
+    });
 }
 
-pub fn add(img: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, color: Rgb<u8>) {
-    let (x, y) = img.dimensions();
-    for i in 0..y {
-        for j in 0..x {
-            img.put_pixel(j, i, rgb_add(*img.get_pixel(j, i), color));
-        }
-    }
+/// Adds `color` to every pixel, saturating at 255.
+pub fn add<T: Channel>(img: &mut ImageBuffer<Rgb<T>, Vec<T>>, color: Rgb<T>)
+where
+    Rgb<T>: Pixel<Subpixel = T>,
+{
+    par::for_each_pixel(img, |pixel| {
+        pixel.0 = [0, 1, 2].map(|c| T::from_value(pixel[c].value() + color[c].value()))
+    });
 }
 
-pub fn to_mc_pic(img: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, width: u32, frame_color: Rgb<u8>) {
+/// Draws a `width` pixels wide frame of `frame_color` around the image.
+pub fn to_mc_pic<P: Pixel>(img: &mut ImageBuffer<P, Vec<P::Subpixel>>, width: u32, frame_color: P) {
     let (x, y) = img.dimensions();
 
     for i in 0..(y - 1) {
         for w in 0..width {
-            img.put_pixel(0 + w, i, frame_color);
+            img.put_pixel(w, i, frame_color);
             img.put_pixel(x - 1 - w, i, frame_color);
         }
     }
     for j in 0..(x - 1) {
         for w in 0..width {
-            img.put_pixel(j, 0 + w, frame_color);
+            img.put_pixel(j, w, frame_color);
             img.put_pixel(j, y - 1 - w, frame_color);
         }
     }
 }
 
-pub fn open_img(path: &str) -> Result<ImageBuffer<Rgb<u8>, Vec<u8>>, image::ImageError> {
-    let img: ImageBuffer<Rgb<u8>, Vec<u8>> = image::ImageReader::open(path)?.decode()?.into_rgb8();
-    return Ok(img);
+/// Opens and decodes the image at `path` as 8-bit RGB.
+pub fn open_img<P: AsRef<Path>>(path: P) -> Result<ImageBuffer<Rgb<u8>, Vec<u8>>, Error> {
+    Ok(open_img_dynamic(path)?.into_rgb8())
+}
+
+/// Opens and decodes the image at `path` as 8-bit RGBA, keeping its
+/// transparency. See [`crate::alpha`] for processing it.
+pub fn open_img_rgba<P: AsRef<Path>>(path: P) -> Result<ImageBuffer<Rgba<u8>, Vec<u8>>, Error> {
+    Ok(open_img_dynamic(path)?.into_rgba8())
+}
+
+/// Opens and decodes the image at `path` as stored, keeping its depth and
+/// transparency, e.g. for [`crate::Pipeline::run`].
+pub fn open_img_dynamic<P: AsRef<Path>>(path: P) -> Result<DynamicImage, Error> {
+    image::ImageReader::open(path)?
+        .decode()
+        .map_err(Error::decode)
+}
+
+/// Saves `img` to `path`, the format being chosen by extension.
+///
+/// The image is first converted to what the format can store: PNG and TIFF
+/// keep 16-bit depth, OpenEXR and Radiance HDR take floats, Farbfeld takes
+/// 16-bit RGBA, ICO 8-bit RGBA, and the other formats (JPEG, PNM, BMP, GIF,
+/// TGA, QOI, WebP, ...) get 8 bits per channel. Float images are saved as
+/// 16-bit to PNG and TIFF. Transparency is kept except in JPEG, PNM and HDR.
+pub fn save_img<I: Into<DynamicImage>>(img: I, path: &str) -> Result<(), Error> {
+    let img: DynamicImage = img.into();
+    let format: Option<ImageFormat> = ImageFormat::from_path(path).ok();
+    let alpha: bool = img.color().has_alpha();
+    let float: bool = matches!(img.color(), ColorType::Rgb32F | ColorType::Rgba32F);
+    let img: DynamicImage = match format {
+        Some(ImageFormat::OpenExr) if alpha => DynamicImage::ImageRgba32F(img.to_rgba32f()),
+        Some(ImageFormat::OpenExr) => DynamicImage::ImageRgb32F(img.to_rgb32f()),
+        Some(ImageFormat::Hdr) => DynamicImage::ImageRgb32F(img.to_rgb32f()),
+        Some(ImageFormat::Farbfeld) => DynamicImage::ImageRgba16(img.to_rgba16()),
+        Some(ImageFormat::Ico) => DynamicImage::ImageRgba8(img.to_rgba8()),
+        Some(ImageFormat::Jpeg | ImageFormat::Pnm) => DynamicImage::ImageRgb8(img.to_rgb8()),
+        Some(ImageFormat::Png | ImageFormat::Tiff) if float => {
+            if alpha {
+                DynamicImage::ImageRgba16(img.to_rgba16())
+            } else {
+                DynamicImage::ImageRgb16(img.to_rgb16())
+            }
+        }
+        Some(ImageFormat::Png | ImageFormat::Tiff) => img,
+        _ => match img {
+            DynamicImage::ImageRgb8(_) | DynamicImage::ImageRgba8(_) => img,
+            _ if alpha => DynamicImage::ImageRgba8(img.to_rgba8()),
+            _ => DynamicImage::ImageRgb8(img.to_rgb8()),
+        },
+    };
+    img.save(path).map_err(Error::encode)
 }
 
-pub fn save_img(img: ImageBuffer<Rgb<u8>, Vec<u8>>, path: &str) -> Result<(), image::ImageError> {
-    let _ = DynamicImage::ImageRgb8(img).save(path);
-    return Ok(());
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pinkize_keeps_the_original_formula() {
+        let colors: [([u8; 3], [u8; 3]); 5] = [
+            ([128, 128, 128], [128, 128, 102]),
+            ([255, 0, 0], [255, 0, 204]),
+            ([10, 200, 30], [200, 10, 160]),
+            ([0, 0, 0], [0, 0, 0]),
+            ([255, 255, 255], [255, 255, 204]),
+        ];
+        let mut img = RgbImage::from_fn(colors.len() as u32, 1, |x, _| Rgb(colors[x as usize].0));
+        pinkize(&mut img);
+        for (x, (_, expected)) in colors.iter().enumerate() {
+            assert_eq!(img.get_pixel(x as u32, 0).0, *expected);
+        }
+    }
+
+    #[test]
+    fn edit_color_measures_per_channel_by_default() {
+        let colors = vec![Rgb([100, 100, 100]), Rgb([255, 0, 0])];
+        let src = RgbImage::from_fn(3, 1, |x, _| {
+            Rgb([[110, 90, 110], [111, 100, 100], [100; 3]][x as usize])
+        });
+        let mut img = src.clone();
+        edit_color(&mut img, colors.clone(), ColorTolerance::PerChannel(10.0));
+        assert_eq!(img.get_pixel(0, 0).0, [255, 0, 0]);
+        assert_eq!(img.get_pixel(1, 0).0, [111, 100, 100]);
+        assert_eq!(img.get_pixel(2, 0).0, [255, 0, 0]);
+        // 17.3 apart in Euclidean distance, so out of reach of the metric.
+        let mut img = src;
+        edit_color(
+            &mut img,
+            colors,
+            ColorTolerance::Metric(ColorMetric::Euclidean, 10.0),
+        );
+        assert_eq!(img.get_pixel(0, 0).0, [110, 90, 110]);
+    }
+
+    #[test]
+    fn save_img_converts_to_what_the_format_stores() {
+        let rgb = RgbImage::from_fn(5, 3, |x, y| Rgb([x as u8 * 50, y as u8 * 100, 255]));
+        let rgba = DynamicImage::ImageRgb8(rgb.clone()).into_rgba8();
+        let depths = [
+            DynamicImage::ImageRgb16(DynamicImage::ImageRgb8(rgb.clone()).into_rgb16()),
+            DynamicImage::ImageRgba16(DynamicImage::ImageRgba8(rgba.clone()).into_rgba16()),
+            DynamicImage::ImageRgb32F(DynamicImage::ImageRgb8(rgb.clone()).into_rgb32f()),
+            DynamicImage::ImageRgba32F(DynamicImage::ImageRgba8(rgba).into_rgba32f()),
+        ];
+        let extensions = [
+            "png", "tiff", "pam", "jpg", "bmp", "gif", "tga", "qoi", "webp", "ico", "ff", "exr",
+            "hdr",
+        ];
+        for extension in extensions {
+            for img in &depths {
+                let path = std::env::temp_dir().join(format!(
+                    "save-{}-{:?}.{}",
+                    std::process::id(),
+                    img.color(),
+                    extension
+                ));
+                let path = path.to_str().unwrap();
+                save_img(img.clone(), path).unwrap_or_else(|err| panic!("{}: {}", path, err));
+                let saved = open_img(path).unwrap();
+                std::fs::remove_file(path).unwrap();
+                assert_eq!(saved.dimensions(), (5, 3), "{}", path);
+                if extension != "jpg" && extension != "hdr" {
+                    assert_eq!(saved, rgb, "{}", path);
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,83 @@
+//! Dithering, color and scaling routines for `image` RGB buffers.
+//!
+//! The processing functions live in [`image_procs`], the saturating pixel
+//! arithmetic and palette parsing helpers they are built on in
+//! [`types_n_convs`]. Their functions are re-exported at the crate root,
+//! together with the `image` crate itself so callers use the same buffer
+//! types. Error diffusion kernels are in [`diffusion`], ordered dithering
+//! threshold maps in [`threshold`], palette extraction in [`quantize`], the
+//! color distances used for palette matching in [`color_dist`], the fast
+//! nearest color lookup built on them in [`nearest`], palette file import
+//! and export in [`palette_io`], built-in hardware palettes in [`palettes`],
+//! brightness/contrast, levels and curves in [`tone`] and hue, saturation,
+//! vibrance and color balance in [`color_adjust`] and gradient maps with
+//! duotone and tritone presets in [`gradient`]. [`resample`] resizes to any
+//! size with box, bilinear, bicubic or Lanczos filtering, [`pixel_art`]
+//! enlarges sprites with Scale2x, EPX, hqx and xBR and shrinks enlarged ones
+//! back along their detected pixel grid.
+//!
+//! The functions work on RGB; [`alpha`] runs RGBA images through them,
+//! preserving, dithering or matting the transparency. Filters are generic
+//! over the channel depth ([`depth`]), so 16-bit and float buffers keep
+//! their precision until the final quantization, and can run in linear
+//! light through [`linear`]. The per-pixel filters run on all cores with
+//! the `parallel` cargo feature, with the same output as the serial build.
+//!
+//! The operations the command line offers are described, parameters
+//! included, by the [`filter::Filter`] trait. [`pipeline`] chains them into
+//! recipes that are saved as TOML or JSON and replayed on other images.
+//!
+//! Everything that can fail returns the one [`Error`] of [`error`], telling
+//! I/O, decoding and encoding failures apart from bad palettes and
+//! parameters.
+
+pub mod alpha;
+pub mod color_adjust;
+pub mod color_dist;
+pub mod depth;
+pub mod diffusion;
+pub mod error;
+pub mod filter;
+pub mod gradient;
+pub mod image_procs;
+pub mod linear;
+pub mod nearest;
+pub mod palette_io;
+pub mod palettes;
+mod par;
+pub mod pipeline;
+pub mod pixel_art;
+pub mod quantize;
+pub mod resample;
+mod rng;
+pub mod threshold;
+pub mod tone;
+pub mod types_n_convs;
+
+pub use alpha::AlphaMode;
+pub use color_adjust::{color_balance, hsl_adjust, hsv_adjust, vibrance};
+pub use color_dist::ColorMetric;
+pub use depth::{convert_depth, open_img_as, Channel};
+pub use diffusion::DiffusionKernel;
+pub use error::Error;
+pub use filter::Filter;
+pub use gradient::{gradient_map, Gradient};
+pub use image;
+pub use image_procs::{
+    add, ascii_ord_bayer_dithering, bright, colorize, downscale, edit_color, open_img,
+    open_img_dynamic, open_img_rgba, ord_bayer_dithering, pinkize, save_img, to_colors, to_mc_pic,
+    to_n_val_channels, twod_errprop_dithering, upscale, write_ascii_ord_bayer_dithering,
+    ColorTolerance, BAYER_8X8,
+};
+pub use nearest::PaletteMatcher;
+pub use palettes::NamedPalette;
+pub use pipeline::Pipeline;
+pub use pixel_art::{downscale_grid, epx, hqx, scale_nx, xbr, CellColor, PixelGrid};
+pub use resample::{resize, resize_to, FitMode, ResampleFilter};
+pub use threshold::ThresholdMap;
+pub use tone::{brightness_contrast, curves, levels, Curve, Pivot};
+pub use types_n_convs::{
+    hsl_to_rgb, hsv_to_rgb, linear_to_srgb, rgb_add, rgb_div, rgb_mul, rgb_sub, rgb_to_arr,
+    rgb_to_hsl, rgb_to_hsv, rgb_to_lab, rgb_to_linear, rgb_to_oklab, srgb_to_linear, str_to_pal,
+    u8_add, u8_div, u8_mul, u8_sub, u8_to_rgb,
+};
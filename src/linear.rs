@@ -0,0 +1,70 @@
+use crate::depth::{convert_depth, Channel};
+use crate::types_n_convs::{linear_to_srgb, srgb_to_linear};
+use image::{ImageBuffer, Pixel, Rgb};
+
+/// Decodes the sRGB image `img` to linear light, `0.0..=1.0` per channel.
+pub fn decode<T: Channel>(img: &ImageBuffer<Rgb<T>, Vec<T>>) -> ImageBuffer<Rgb<f32>, Vec<f32>>
+where
+    Rgb<T>: Pixel<Subpixel = T>,
+{
+    ImageBuffer::from_fn(img.width(), img.height(), |x, y| {
+        Rgb(img
+            .get_pixel(x, y)
+            .0
+            .map(|v| srgb_to_linear(v.value() / T::MAX)))
+    })
+}
+
+/// Encodes the linear light image `img` as sRGB of depth `T`, rounding and
+/// clamping for integer depths.
+pub fn encode<T: Channel>(mut img: ImageBuffer<Rgb<f32>, Vec<f32>>) -> ImageBuffer<Rgb<T>, Vec<T>>
+where
+    Rgb<T>: Pixel<Subpixel = T>,
+{
+    for pixel in img.pixels_mut() {
+        pixel.0 = pixel.0.map(linear_to_srgb);
+    }
+    convert_depth::<f32, T>(img)
+}
+
+/// Runs `f` on `img` in linear light: the image is decoded, handed to `f`
+/// as a float buffer and re-encoded to its own depth.
+///
+/// The per-pixel filters take any depth, so they can run here unchanged,
+/// e.g. `in_linear(img, |mut img| { bright(&mut img, 1.5); img })`.
+/// Colors passed to them should be linear too, see
+/// [`crate::rgb_to_linear`].
+pub fn in_linear<T: Channel, F>(
+    img: ImageBuffer<Rgb<T>, Vec<T>>,
+    f: F,
+) -> ImageBuffer<Rgb<T>, Vec<T>>
+where
+    Rgb<T>: Pixel<Subpixel = T>,
+    F: FnOnce(ImageBuffer<Rgb<f32>, Vec<f32>>) -> ImageBuffer<Rgb<f32>, Vec<f32>>,
+{
+    encode(f(decode(&img)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbImage;
+
+    #[test]
+    fn srgb_and_linear_round_trip() {
+        for v in 0..=255_u8 {
+            let v: f32 = v as f32 / 255.0;
+            let back: f32 = linear_to_srgb(srgb_to_linear(v));
+            assert!((back - v).abs() < 1e-5, "{} came back as {}", v, back);
+        }
+        assert_eq!(srgb_to_linear(0.0), 0.0);
+        assert!((srgb_to_linear(1.0) - 1.0).abs() < 1e-6);
+        assert!((srgb_to_linear(0.5) - 0.214).abs() < 1e-3);
+        let img = RgbImage::from_fn(16, 16, |x, y| Rgb([(x * 16 + y) as u8, x as u8, 255]));
+        assert_eq!(encode::<u8>(decode(&img)), img);
+        let deep = ImageBuffer::<Rgb<u16>, Vec<u16>>::from_fn(256, 1, |x, _| {
+            Rgb([x as u16 * 257, 65535 - x as u16 * 257, x as u16])
+        });
+        assert_eq!(encode::<u16>(decode(&deep)), deep);
+    }
+}
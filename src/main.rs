@@ -1,44 +1,65 @@
-mod image_procs;
-mod types_n_convs;
+mod cli;
+#[cfg(feature = "gui")]
 mod ui;
 
-use druid::piet::ImageBuf;
+#[cfg(feature = "gui")]
 use druid::{AppLauncher, Data, Lens, LocalizedString, WindowDesc};
-use image::{ImageBuffer, Rgb};
+#[cfg(feature = "gui")]
+use rust_image_processing::{filter::FILTERS, image::DynamicImage};
+use std::process::ExitCode;
+#[cfg(feature = "gui")]
 use std::sync::{Arc, Mutex};
-use ui::{ColorParams, DitheringParams, ProcessingOption};
+#[cfg(feature = "gui")]
+use ui::ParamValues;
 
+/// An image shared between the app state, the undo stack and the view.
+#[cfg(feature = "gui")]
+pub type SharedImage = Arc<Mutex<DynamicImage>>;
+
+#[cfg(feature = "gui")]
 #[derive(Clone, Data, Lens)]
 pub struct AppState {
-    pub img: Option<Arc<Mutex<ImageBuffer<Rgb<u8>, Vec<u8>>>>>,
-    pub undos: Arc<Mutex<Vec<ImageBuf>>>,
+    pub img: Option<SharedImage>,
+    /// The image as opened with every applied step, which the selected
+    /// filter is previewed on.
+    pub base: Option<SharedImage>,
+    /// Images replaced by applying a step or opening another image, most
+    /// recent last.
+    pub undos: Arc<Mutex<Vec<SharedImage>>>,
 
-    pub selected_option: ProcessingOption,
+    /// Index of the selected filter in [`FILTERS`].
+    pub filter: usize,
+    pub values: ParamValues,
+    /// Why the selected filter cannot run, empty when it can.
+    pub error: String,
+}
 
-    pub dithering_params: DitheringParams,
-    pub color_params: ColorParams,
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    #[cfg(feature = "gui")]
+    if args.is_empty() {
+        return launch_gui();
+    }
+    cli::run(&args)
 }
 
-fn main() {
+#[cfg(feature = "gui")]
+fn launch_gui() -> ExitCode {
     let main_window = WindowDesc::new(ui::build_ui())
         .title(LocalizedString::new("Image Processing"))
         .window_size((800.0, 500.0));
 
     let initial_state = AppState {
         img: None,
+        base: None,
         undos: Arc::new(Mutex::new(Vec::new())),
-        selected_option: ProcessingOption::Dithering,
-        dithering_params: DitheringParams {
-            palette: "".to_string(),
-            pixel_size: 0.0,
-        },
-        color_params: ColorParams {
-            brightness: 1.0,
-            contrast: 1.0,
-        },
+        filter: 0,
+        values: ParamValues::defaults(FILTERS[0]),
+        error: String::new(),
     };
 
     AppLauncher::with_window(main_window)
         .launch(initial_state)
         .expect("Failed to launch");
+    ExitCode::SUCCESS
 }
@@ -0,0 +1,195 @@
+use crate::color_dist::{dist2, ColorMetric};
+use crate::error::Error;
+
+/// Index of the color of `palette` closest to `color` under `metric`,
+/// found by checking every entry. Ties go to the lowest index.
+///
+/// This is the reference [`PaletteMatcher`] agrees with; it is kept public
+/// for comparisons and benchmarks.
+pub fn nearest_index_brute(palette: &[[u8; 3]], color: [u8; 3], metric: ColorMetric) -> usize {
+    let mut best: usize = 0;
+    let mut best_key: f32 = f32::INFINITY;
+    for (i, &entry) in palette.iter().enumerate() {
+        let key: f32 = metric.key(entry, color);
+        if key < best_key {
+            best_key = key;
+            best = i;
+        }
+    }
+    best
+}
+
+struct KdNode {
+    point: [f32; 3],
+    index: usize,
+    axis: usize,
+    left: Option<usize>,
+    right: Option<usize>,
+}
+
+/// Nearest palette color lookup, built once per palette and metric.
+///
+/// Metrics that are Euclidean in some color space (see
+/// [`ColorMetric::coords`]) are searched with a k-d tree over the palette
+/// converted to that space; `Redmean` and `Ciede2000` fall back to a linear
+/// scan. Either way the result is exactly that of [`nearest_index_brute`].
+pub struct PaletteMatcher {
+    palette: Vec<[u8; 3]>,
+    metric: ColorMetric,
+    nodes: Vec<KdNode>,
+    root: Option<usize>,
+}
+
+impl PaletteMatcher {
+    /// Builds the lookup for `palette`; fails with
+    /// [`Error::InvalidPalette`] for an empty palette.
+    pub fn new(palette: Vec<[u8; 3]>, metric: ColorMetric) -> Result<PaletteMatcher, Error> {
+        if palette.is_empty() {
+            return Err(Error::InvalidPalette("empty palette".to_string()));
+        }
+        let mut matcher = PaletteMatcher {
+            palette,
+            metric,
+            nodes: vec![],
+            root: None,
+        };
+        let points: Option<Vec<([f32; 3], usize)>> = matcher
+            .palette
+            .iter()
+            .enumerate()
+            .map(|(i, &color)| Some((metric.coords(color)?, i)))
+            .collect();
+        if let Some(mut points) = points {
+            matcher.root = matcher.build(&mut points, 0);
+        }
+        Ok(matcher)
+    }
+
+    fn build(&mut self, points: &mut [([f32; 3], usize)], depth: usize) -> Option<usize> {
+        if points.is_empty() {
+            return None;
+        }
+        let axis: usize = depth % 3;
+        points.sort_by(|a, b| a.0[axis].total_cmp(&b.0[axis]).then(a.1.cmp(&b.1)));
+        let mid: usize = points.len() / 2;
+        let (point, index) = points[mid];
+        let (below, rest) = points.split_at_mut(mid);
+        let left = self.build(below, depth + 1);
+        let right = self.build(&mut rest[1..], depth + 1);
+        self.nodes.push(KdNode {
+            point,
+            index,
+            axis,
+            left,
+            right,
+        });
+        Some(self.nodes.len() - 1)
+    }
+
+    fn search(&self, node: Option<usize>, target: [f32; 3], best: &mut (f32, usize)) {
+        let node = match node {
+            Some(node) => &self.nodes[node],
+            None => return,
+        };
+        let key: f32 = dist2(node.point, target);
+        if key < best.0 || (key == best.0 && node.index < best.1) {
+            *best = (key, node.index);
+        }
+        let delta: f32 = target[node.axis] - node.point[node.axis];
+        let (near, far) = if delta < 0.0 {
+            (node.left, node.right)
+        } else {
+            (node.right, node.left)
+        };
+        self.search(near, target, best);
+        if delta * delta <= best.0 {
+            self.search(far, target, best);
+        }
+    }
+
+    pub fn palette(&self) -> &[[u8; 3]] {
+        &self.palette
+    }
+
+    pub fn metric(&self) -> ColorMetric {
+        self.metric
+    }
+
+    /// Index of the palette color closest to `color`.
+    pub fn nearest_index(&self, color: [u8; 3]) -> usize {
+        match self.metric.coords(color) {
+            Some(target) if self.root.is_some() => {
+                let mut best: (f32, usize) = (f32::INFINITY, 0);
+                self.search(self.root, target, &mut best);
+                best.1
+            }
+            _ => nearest_index_brute(&self.palette, color, self.metric),
+        }
+    }
+
+    /// Palette color closest to `color`.
+    pub fn nearest(&self, color: [u8; 3]) -> [u8; 3] {
+        self.palette[self.nearest_index(color)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::SplitMix64;
+
+    fn check(palette: Vec<[u8; 3]>, queries: &[[u8; 3]]) {
+        for metric in ColorMetric::ALL {
+            let matcher = PaletteMatcher::new(palette.clone(), metric).unwrap();
+            for &color in queries {
+                assert_eq!(
+                    matcher.nearest_index(color),
+                    nearest_index_brute(&palette, color, metric),
+                    "{:?} in {:?} under {}",
+                    color,
+                    palette,
+                    metric.name()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn matches_brute_force_on_random_palettes() {
+        let mut rng = SplitMix64::new(9);
+        let mut color = |levels: &[u8]| [0; 3].map(|_: u8| levels[rng.below(levels.len())]);
+        let full: Vec<u8> = (0..=255).collect();
+        let queries: Vec<[u8; 3]> = (0..300).map(|_| color(&full)).collect();
+        for size in [1, 2, 5, 16, 64] {
+            check((0..size).map(|_| color(&full)).collect(), &queries);
+        }
+        // Few levels, so palettes repeat colors and queries often sit
+        // exactly between two of them.
+        let coarse: [u8; 5] = [0, 64, 128, 192, 255];
+        let grid: Vec<[u8; 3]> = (0..125)
+            .map(|i| [coarse[i / 25], coarse[i / 5 % 5], coarse[i % 5]])
+            .chain((0..125).map(|i| {
+                [
+                    (i / 25 * 32) as u8,
+                    (i / 5 % 5 * 32) as u8,
+                    (i % 5 * 32) as u8,
+                ]
+            }))
+            .collect();
+        for size in [3, 8, 20, 40] {
+            check((0..size).map(|_| color(&coarse)).collect(), &grid);
+        }
+    }
+
+    #[test]
+    fn ties_and_duplicates_go_to_the_lowest_index() {
+        let palette = vec![[10, 10, 10], [0, 0, 0], [20, 0, 0], [0, 0, 0], [10, 10, 10]];
+        check(
+            palette.clone(),
+            &[[10, 0, 0], [0, 0, 0], [10, 10, 10], [5, 5, 5]],
+        );
+        let matcher = PaletteMatcher::new(palette, ColorMetric::Euclidean).unwrap();
+        assert_eq!(matcher.nearest_index([10, 0, 0]), 1);
+        assert_eq!(matcher.nearest_index([10, 10, 10]), 0);
+    }
+}
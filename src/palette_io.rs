@@ -0,0 +1,567 @@
+use std::fs;
+use std::path::Path;
+
+use crate::error::Error;
+
+/// Palette file formats understood by [`load`] and [`save`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PaletteFormat {
+    /// GIMP palette (`.gpl`).
+    Gpl,
+    /// Adobe Swatch Exchange (`.ase`).
+    Ase,
+    /// Adobe Photoshop color swatches (`.aco`).
+    Aco,
+    /// Paint.NET palette (`.txt`), one `AARRGGBB` value per line.
+    PaintNet,
+    /// JASC / Paint Shop Pro palette (`.pal`).
+    Jasc,
+    /// Lospec hex list (`.hex`), one `RRGGBB` value per line.
+    Hex,
+}
+
+impl PaletteFormat {
+    pub const ALL: [PaletteFormat; 6] = [
+        PaletteFormat::Gpl,
+        PaletteFormat::Ase,
+        PaletteFormat::Aco,
+        PaletteFormat::PaintNet,
+        PaletteFormat::Jasc,
+        PaletteFormat::Hex,
+    ];
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            PaletteFormat::Gpl => "gpl",
+            PaletteFormat::Ase => "ase",
+            PaletteFormat::Aco => "aco",
+            PaletteFormat::PaintNet => "txt",
+            PaletteFormat::Jasc => "pal",
+            PaletteFormat::Hex => "hex",
+        }
+    }
+
+    /// Picks the format from the extension of `path`, ignoring case.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<PaletteFormat> {
+        let ext = path.as_ref().extension()?.to_str()?;
+        PaletteFormat::ALL
+            .into_iter()
+            .find(|format| format.extension().eq_ignore_ascii_case(ext))
+    }
+}
+
+/// A line of a text palette that could not be parsed; `line` is 0-based.
+fn line_err(line: usize, message: impl Into<String>) -> Error {
+    Error::InvalidPalette(format!("line {}: {}", line + 1, message.into()))
+}
+
+/// A file that does not follow its format, e.g. a bad header or a
+/// truncated binary palette.
+fn malformed(message: impl Into<String>) -> Error {
+    Error::InvalidPalette(message.into())
+}
+
+fn text(bytes: &[u8]) -> Result<&str, Error> {
+    let text = std::str::from_utf8(bytes).map_err(|_| malformed("palette is not valid UTF-8"))?;
+    Ok(text.strip_prefix('\u{feff}').unwrap_or(text))
+}
+
+/// Parses `digits` (exactly 6 hex digits) as `RRGGBB`.
+fn hex_color(digits: &str) -> Option<[u8; 3]> {
+    if digits.len() != 6 || !digits.bytes().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&digits[i..i + 2], 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+fn unit_to_u8(v: f32) -> u8 {
+    (v.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+fn cmyk_to_rgb(c: f32, m: f32, y: f32, k: f32) -> [u8; 3] {
+    [
+        unit_to_u8((1.0 - c) * (1.0 - k)),
+        unit_to_u8((1.0 - m) * (1.0 - k)),
+        unit_to_u8((1.0 - y) * (1.0 - k)),
+    ]
+}
+
+/// Hue in degrees, saturation and brightness in `0..1`.
+fn hsb_to_rgb(h: f32, s: f32, v: f32) -> [u8; 3] {
+    let h: f32 = h.rem_euclid(360.0) / 60.0;
+    let c: f32 = v * s;
+    let x: f32 = c * (1.0 - (h % 2.0 - 1.0).abs());
+    let (r, g, b) = match h as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    let m: f32 = v - c;
+    [unit_to_u8(r + m), unit_to_u8(g + m), unit_to_u8(b + m)]
+}
+
+/// Big-endian reader over a binary palette.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], Error> {
+        if self.bytes.len() < n {
+            return Err(malformed("unexpected end of palette file"));
+        }
+        let (head, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Ok(head)
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        let b = self.take(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        let b = self.take(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn f32(&mut self) -> Result<f32, Error> {
+        Ok(f32::from_bits(self.u32()?))
+    }
+}
+
+/// Writes `name` as big-endian UTF-16 code units followed by a terminator.
+fn push_utf16(bytes: &mut Vec<u8>, name: &str) {
+    for unit in name.encode_utf16().chain([0]) {
+        bytes.extend_from_slice(&unit.to_be_bytes());
+    }
+}
+
+/// `pal` has more colors than a `format` file can count.
+fn too_many(pal: &[[u8; 3]], format: &str, max: impl std::fmt::Display) -> Error {
+    Error::InvalidPalette(format!(
+        "{} files hold at most {} colors, not {}",
+        format,
+        max,
+        pal.len()
+    ))
+}
+
+fn color_name(color: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2])
+}
+
+/// Parses a GIMP palette. Color names, `Name:`/`Columns:` headers and `#`
+/// comments are skipped.
+pub fn from_gpl(text: &str) -> Result<Vec<[u8; 3]>, Error> {
+    let mut lines = text.lines().enumerate();
+    match lines.next() {
+        Some((_, header)) if header.trim() == "GIMP Palette" => {}
+        _ => return Err(line_err(0, "missing `GIMP Palette` header")),
+    }
+    let mut pal: Vec<[u8; 3]> = vec![];
+    for (i, line) in lines {
+        let line: &str = line.trim();
+        if line.is_empty()
+            || line.starts_with('#')
+            || line.starts_with("Name:")
+            || line.starts_with("Columns:")
+        {
+            continue;
+        }
+        let mut channels = line.split_whitespace().map(|v| v.parse::<u8>().ok());
+        match (channels.next(), channels.next(), channels.next()) {
+            (Some(Some(r)), Some(Some(g)), Some(Some(b))) => pal.push([r, g, b]),
+            _ => {
+                return Err(line_err(
+                    i,
+                    format!("expected `R G B [name]`, got `{}`", line),
+                ))
+            }
+        }
+    }
+    Ok(pal)
+}
+
+/// Writes a GIMP palette titled `name`, naming each color by its hex value.
+pub fn to_gpl(pal: &[[u8; 3]], name: &str) -> String {
+    let mut out: String = format!("GIMP Palette\nName: {}\nColumns: 0\n#\n", name);
+    for &color in pal {
+        out += &format!(
+            "{:3} {:3} {:3}\t{}\n",
+            color[0],
+            color[1],
+            color[2],
+            color_name(color)
+        );
+    }
+    out
+}
+
+/// Parses an Adobe Swatch Exchange file. Groups are flattened; RGB, CMYK
+/// and grey swatches are converted to sRGB bytes, LAB swatches are
+/// rejected.
+pub fn from_ase(bytes: &[u8]) -> Result<Vec<[u8; 3]>, Error> {
+    let mut reader = Reader { bytes };
+    if reader.take(4)? != b"ASEF" {
+        return Err(malformed("missing `ASEF` signature"));
+    }
+    reader.take(4)?;
+    let blocks: u32 = reader.u32()?;
+    let mut pal: Vec<[u8; 3]> = vec![];
+    for _ in 0..blocks {
+        let kind: u16 = reader.u16()?;
+        let len: usize = reader.u32()? as usize;
+        let mut block = Reader {
+            bytes: reader.take(len)?,
+        };
+        if kind != 0x0001 {
+            continue;
+        }
+        let name_len: usize = block.u16()? as usize;
+        block.take(2 * name_len)?;
+        let model = block.take(4)?;
+        let color: [u8; 3] = match model {
+            b"RGB " => [
+                unit_to_u8(block.f32()?),
+                unit_to_u8(block.f32()?),
+                unit_to_u8(block.f32()?),
+            ],
+            b"CMYK" => cmyk_to_rgb(block.f32()?, block.f32()?, block.f32()?, block.f32()?),
+            b"Gray" => [unit_to_u8(block.f32()?); 3],
+            _ => {
+                return Err(malformed(format!(
+                    "unsupported ASE color model `{}`",
+                    String::from_utf8_lossy(model).trim()
+                )))
+            }
+        };
+        pal.push(color);
+    }
+    Ok(pal)
+}
+
+/// Writes an Adobe Swatch Exchange file of RGB swatches named by their hex
+/// value. Palettes of more than `u32::MAX` colors do not fit the format.
+pub fn to_ase(pal: &[[u8; 3]]) -> Result<Vec<u8>, Error> {
+    let count: u32 = u32::try_from(pal.len()).map_err(|_| too_many(pal, "ASE", u32::MAX))?;
+    let mut bytes: Vec<u8> = b"ASEF".to_vec();
+    bytes.extend_from_slice(&[0, 1, 0, 0]);
+    bytes.extend_from_slice(&count.to_be_bytes());
+    for &color in pal {
+        let name: String = color_name(color);
+        let mut block: Vec<u8> = (name.len() as u16 + 1).to_be_bytes().to_vec();
+        push_utf16(&mut block, &name);
+        block.extend_from_slice(b"RGB ");
+        for v in color {
+            block.extend_from_slice(&(v as f32 / 255.0).to_be_bytes());
+        }
+        block.extend_from_slice(&2_u16.to_be_bytes());
+        bytes.extend_from_slice(&1_u16.to_be_bytes());
+        bytes.extend_from_slice(&(block.len() as u32).to_be_bytes());
+        bytes.extend(block);
+    }
+    Ok(bytes)
+}
+
+/// Parses Photoshop color swatches. Only the first section is read, which
+/// is the unnamed version 1 list in files with both. RGB, HSB, CMYK and
+/// grayscale swatches are converted, other color spaces are rejected.
+pub fn from_aco(bytes: &[u8]) -> Result<Vec<[u8; 3]>, Error> {
+    let mut reader = Reader { bytes };
+    let version: u16 = reader.u16()?;
+    if version != 1 && version != 2 {
+        return Err(malformed(format!("unsupported ACO version {}", version)));
+    }
+    let count: u16 = reader.u16()?;
+    let mut pal: Vec<[u8; 3]> = vec![];
+    for _ in 0..count {
+        let space: u16 = reader.u16()?;
+        let [w, x, y, z] = [reader.u16()?, reader.u16()?, reader.u16()?, reader.u16()?];
+        let unit = |v: u16| v as f32 / 65535.0;
+        pal.push(match space {
+            0 => [(w >> 8) as u8, (x >> 8) as u8, (y >> 8) as u8],
+            1 => hsb_to_rgb(unit(w) * 360.0, unit(x), unit(y)),
+            2 => cmyk_to_rgb(1.0 - unit(w), 1.0 - unit(x), 1.0 - unit(y), 1.0 - unit(z)),
+            8 => [unit_to_u8(1.0 - w.min(10000) as f32 / 10000.0); 3],
+            _ => return Err(malformed(format!("unsupported ACO color space {}", space))),
+        });
+        if version == 2 {
+            reader.take(2)?;
+            let name_len: usize = reader.u16()? as usize;
+            reader.take(2 * name_len)?;
+        }
+    }
+    Ok(pal)
+}
+
+/// Writes Photoshop color swatches: an RGB version 1 section followed by
+/// the same colors as a version 2 section named by their hex value.
+/// Palettes of more than 65535 colors do not fit the format.
+pub fn to_aco(pal: &[[u8; 3]]) -> Result<Vec<u8>, Error> {
+    let count: u16 = u16::try_from(pal.len()).map_err(|_| too_many(pal, "ACO", u16::MAX))?;
+    let mut bytes: Vec<u8> = vec![];
+    for version in [1_u16, 2] {
+        bytes.extend_from_slice(&version.to_be_bytes());
+        bytes.extend_from_slice(&count.to_be_bytes());
+        for &color in pal {
+            bytes.extend_from_slice(&[0, 0]);
+            for v in color {
+                bytes.extend_from_slice(&(v as u16 * 257).to_be_bytes());
+            }
+            bytes.extend_from_slice(&[0, 0]);
+            if version == 2 {
+                let name: String = color_name(color);
+                bytes.extend_from_slice(&(name.len() as u32 + 1).to_be_bytes());
+                push_utf16(&mut bytes, &name);
+            }
+        }
+    }
+    Ok(bytes)
+}
+
+/// Parses a Paint.NET palette: `;` comments and one `AARRGGBB` value per
+/// line. Alpha is ignored.
+pub fn from_paint_net(text: &str) -> Result<Vec<[u8; 3]>, Error> {
+    let mut pal: Vec<[u8; 3]> = vec![];
+    for (i, line) in text.lines().enumerate() {
+        let line: &str = line.trim();
+        if line.is_empty() || line.starts_with(';') {
+            continue;
+        }
+        // `get` rather than indexing: a line of 8 bytes may not be ASCII.
+        let color = match (line.len(), line.get(..2), line.get(2..)) {
+            (8, Some(alpha), Some(rgb)) if alpha.bytes().all(|c| c.is_ascii_hexdigit()) => {
+                hex_color(rgb)
+            }
+            _ => None,
+        };
+        pal.push(color.ok_or_else(|| line_err(i, format!("expected `AARRGGBB`, got `{}`", line)))?);
+    }
+    Ok(pal)
+}
+
+/// Writes a Paint.NET palette with every color fully opaque.
+pub fn to_paint_net(pal: &[[u8; 3]]) -> String {
+    let mut out: String = String::from(";paint.net Palette File\n");
+    for color in pal {
+        out += &format!("FF{:02X}{:02X}{:02X}\n", color[0], color[1], color[2]);
+    }
+    out
+}
+
+/// Parses a JASC palette: `JASC-PAL`, `0100`, the color count, then one
+/// `R G B` line per color.
+pub fn from_jasc(text: &str) -> Result<Vec<[u8; 3]>, Error> {
+    let mut lines = text.lines().map(str::trim).enumerate();
+    let mut expect = |what: &str| match lines.next() {
+        Some((_, line)) if line == what => Ok(()),
+        Some((i, line)) => Err(line_err(i, format!("expected `{}`, got `{}`", what, line))),
+        None => Err(malformed(format!("missing `{}`", what))),
+    };
+    expect("JASC-PAL")?;
+    expect("0100")?;
+    let count: usize = match lines.next() {
+        Some((i, line)) => line
+            .parse()
+            .map_err(|_| line_err(i, format!("expected color count, got `{}`", line)))?,
+        None => return Err(malformed("missing color count")),
+    };
+    let mut pal: Vec<[u8; 3]> = vec![];
+    for (i, line) in lines.filter(|(_, line)| !line.is_empty()) {
+        let channels: Vec<Option<u8>> = line.split_whitespace().map(|v| v.parse().ok()).collect();
+        match channels.as_slice() {
+            [Some(r), Some(g), Some(b)] => pal.push([*r, *g, *b]),
+            _ => return Err(line_err(i, format!("expected `R G B`, got `{}`", line))),
+        }
+    }
+    if pal.len() != count {
+        return Err(malformed(format!(
+            "palette declares {} colors but has {}",
+            count,
+            pal.len()
+        )));
+    }
+    Ok(pal)
+}
+
+/// Writes a JASC palette with CRLF line endings, as Paint Shop Pro does.
+pub fn to_jasc(pal: &[[u8; 3]]) -> String {
+    let mut out: String = format!("JASC-PAL\r\n0100\r\n{}\r\n", pal.len());
+    for color in pal {
+        out += &format!("{} {} {}\r\n", color[0], color[1], color[2]);
+    }
+    out
+}
+
+/// Parses a Lospec hex list: one `RRGGBB` value per line, optionally
+/// prefixed with `#`.
+pub fn from_hex(text: &str) -> Result<Vec<[u8; 3]>, Error> {
+    let mut pal: Vec<[u8; 3]> = vec![];
+    for (i, line) in text.lines().enumerate() {
+        let line: &str = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let digits: &str = line.strip_prefix('#').unwrap_or(line);
+        pal.push(
+            hex_color(digits)
+                .ok_or_else(|| line_err(i, format!("expected `RRGGBB`, got `{}`", line)))?,
+        );
+    }
+    Ok(pal)
+}
+
+/// Writes a Lospec hex list.
+pub fn to_hex(pal: &[[u8; 3]]) -> String {
+    pal.iter()
+        .map(|color| format!("{:02x}{:02x}{:02x}\n", color[0], color[1], color[2]))
+        .collect()
+}
+
+/// Parses `bytes` as a palette file in `format`.
+pub fn parse(bytes: &[u8], format: PaletteFormat) -> Result<Vec<[u8; 3]>, Error> {
+    match format {
+        PaletteFormat::Gpl => from_gpl(text(bytes)?),
+        PaletteFormat::Ase => from_ase(bytes),
+        PaletteFormat::Aco => from_aco(bytes),
+        PaletteFormat::PaintNet => from_paint_net(text(bytes)?),
+        PaletteFormat::Jasc => from_jasc(text(bytes)?),
+        PaletteFormat::Hex => from_hex(text(bytes)?),
+    }
+}
+
+/// Serializes `pal` as a palette file in `format`; GIMP palettes are
+/// titled `name`. Fails if the binary formats cannot count the colors.
+pub fn serialize(pal: &[[u8; 3]], format: PaletteFormat, name: &str) -> Result<Vec<u8>, Error> {
+    Ok(match format {
+        PaletteFormat::Gpl => to_gpl(pal, name).into_bytes(),
+        PaletteFormat::Ase => to_ase(pal)?,
+        PaletteFormat::Aco => to_aco(pal)?,
+        PaletteFormat::PaintNet => to_paint_net(pal).into_bytes(),
+        PaletteFormat::Jasc => to_jasc(pal).into_bytes(),
+        PaletteFormat::Hex => to_hex(pal).into_bytes(),
+    })
+}
+
+fn format_of(path: &Path) -> Result<PaletteFormat, Error> {
+    PaletteFormat::from_path(path).ok_or_else(|| {
+        Error::InvalidPalette(format!("unknown palette format: `{}`", path.display()))
+    })
+}
+
+/// Loads a palette file, picking the format by extension.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Vec<[u8; 3]>, Error> {
+    let format: PaletteFormat = format_of(path.as_ref())?;
+    parse(&fs::read(path)?, format)
+}
+
+/// Saves `pal`, picking the format by extension. GIMP palettes are titled
+/// with the file stem.
+pub fn save<P: AsRef<Path>>(pal: &[[u8; 3]], path: P) -> Result<(), Error> {
+    let path: &Path = path.as_ref();
+    let format: PaletteFormat = format_of(path)?;
+    let name = path.file_stem().map(|stem| stem.to_string_lossy());
+    fs::write(path, serialize(pal, format, name.as_deref().unwrap_or(""))?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAL: [[u8; 3]; 3] = [[0, 0, 0], [255, 128, 7], [18, 52, 86]];
+
+    fn be16(values: &[u16]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_be_bytes()).collect()
+    }
+
+    /// An ASE block of `kind` holding `body`.
+    fn ase_block(kind: u16, body: &[u8]) -> Vec<u8> {
+        let mut block: Vec<u8> = be16(&[kind]);
+        block.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        block.extend_from_slice(body);
+        block
+    }
+
+    /// `PAL` as other programs write it, one sample per format.
+    fn sample(format: PaletteFormat) -> Vec<u8> {
+        match format {
+            PaletteFormat::Gpl => b"GIMP Palette\nName: Test\nColumns: 4\n# made by hand\n\
+                  0   0   0\tBlack\n255 128   7 Orange\n18 52 86\n"
+                .to_vec(),
+            PaletteFormat::PaintNet => {
+                b";paint.net Palette File\nFF000000\nffff8007\n80123456\n".to_vec()
+            }
+            PaletteFormat::Jasc => {
+                b"JASC-PAL\r\n0100\r\n3\r\n0 0 0\r\n255 128 7\r\n18 52 86\r\n".to_vec()
+            }
+            PaletteFormat::Hex => b"000000\n#FF8007\n\n123456\n".to_vec(),
+            PaletteFormat::Aco => {
+                let mut words: Vec<u16> = vec![1, 3];
+                for color in PAL {
+                    let [r, g, b] = color.map(|v| v as u16 * 257);
+                    words.extend([0, r, g, b, 0]);
+                }
+                be16(&words)
+            }
+            PaletteFormat::Ase => {
+                let mut bytes: Vec<u8> = b"ASEF".to_vec();
+                bytes.extend(be16(&[1, 0]));
+                bytes.extend_from_slice(&5_u32.to_be_bytes());
+                let mut group: Vec<u8> = be16(&[2]);
+                push_utf16(&mut group, "g");
+                bytes.extend(ase_block(0xc001, &group));
+                for color in PAL {
+                    let mut body: Vec<u8> = be16(&[1, 0]);
+                    body.extend_from_slice(b"RGB ");
+                    for v in color {
+                        body.extend_from_slice(&(v as f32 / 255.0).to_be_bytes());
+                    }
+                    body.extend(be16(&[0]));
+                    bytes.extend(ase_block(0x0001, &body));
+                }
+                bytes.extend(ase_block(0xc002, &[]));
+                bytes
+            }
+        }
+    }
+
+    #[test]
+    fn every_format_round_trips() {
+        for format in PaletteFormat::ALL {
+            let pal = parse(&sample(format), format).unwrap();
+            assert_eq!(pal, PAL, "{:?}", format);
+            let bytes = serialize(&pal, format, "Test").unwrap();
+            assert_eq!(parse(&bytes, format).unwrap(), PAL, "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn aco_rejects_more_colors_than_it_can_count() {
+        let pal: Vec<[u8; 3]> = (0..=u16::MAX as u32)
+            .map(|i| [(i >> 8) as u8, i as u8, 0])
+            .collect();
+        assert_eq!(from_aco(&to_aco(&pal[1..]).unwrap()).unwrap(), &pal[1..]);
+        assert!(matches!(to_aco(&pal), Err(Error::InvalidPalette(_))));
+        assert!(serialize(&pal, PaletteFormat::Aco, "").is_err());
+    }
+
+    #[test]
+    fn paint_net_reports_non_ascii_lines() {
+        assert_eq!(
+            from_paint_net("FF102030\nff0a0b0c").unwrap(),
+            [[0x10, 0x20, 0x30], [0x0a, 0x0b, 0x0c]]
+        );
+        for line in ["\u{20ac}12345", "1\u{20ac}2345", "FF10203\u{e9}"] {
+            match from_paint_net(line) {
+                Err(Error::InvalidPalette(message)) => assert!(message.starts_with("line 1:")),
+                other => panic!("`{}` gave {:?}", line, other),
+            }
+        }
+    }
+}
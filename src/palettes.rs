@@ -0,0 +1,255 @@
+use crate::types_n_convs::normalize_name;
+
+/// A palette of some classic hardware, picked by name.
+pub struct NamedPalette {
+    pub name: &'static str,
+    pub colors: &'static [[u8; 3]],
+}
+
+pub const GAME_BOY: NamedPalette = NamedPalette {
+    name: "game-boy",
+    colors: &[
+        [0x0f, 0x38, 0x0f],
+        [0x30, 0x62, 0x30],
+        [0x8b, 0xac, 0x0f],
+        [0x9b, 0xbc, 0x0f],
+    ],
+};
+
+/// The 16 colors of CGA text mode, with the RGBI brown.
+pub const CGA: NamedPalette = NamedPalette {
+    name: "cga",
+    colors: &[
+        [0x00, 0x00, 0x00],
+        [0x00, 0x00, 0xaa],
+        [0x00, 0xaa, 0x00],
+        [0x00, 0xaa, 0xaa],
+        [0xaa, 0x00, 0x00],
+        [0xaa, 0x00, 0xaa],
+        [0xaa, 0x55, 0x00],
+        [0xaa, 0xaa, 0xaa],
+        [0x55, 0x55, 0x55],
+        [0x55, 0x55, 0xff],
+        [0x55, 0xff, 0x55],
+        [0x55, 0xff, 0xff],
+        [0xff, 0x55, 0x55],
+        [0xff, 0x55, 0xff],
+        [0xff, 0xff, 0x55],
+        [0xff, 0xff, 0xff],
+    ],
+};
+
+/// EGA color `i` of 64: bits 0-2 add `0xaa` to blue, green and red, bits
+/// 3-5 add `0x55`.
+const fn ega() -> [[u8; 3]; 64] {
+    const fn channel(i: usize, hi: usize, lo: usize) -> u8 {
+        ((i >> hi) & 1) as u8 * 0xaa + ((i >> lo) & 1) as u8 * 0x55
+    }
+
+    let mut colors: [[u8; 3]; 64] = [[0; 3]; 64];
+    let mut i: usize = 0;
+    while i < 64 {
+        colors[i] = [channel(i, 2, 5), channel(i, 1, 4), channel(i, 0, 3)];
+        i += 1;
+    }
+    colors
+}
+
+/// The full 64 color EGA palette, in hardware order.
+pub const EGA: NamedPalette = NamedPalette {
+    name: "ega",
+    colors: &ega(),
+};
+
+pub const PICO_8: NamedPalette = NamedPalette {
+    name: "pico-8",
+    colors: &[
+        [0x00, 0x00, 0x00],
+        [0x1d, 0x2b, 0x53],
+        [0x7e, 0x25, 0x53],
+        [0x00, 0x87, 0x51],
+        [0xab, 0x52, 0x36],
+        [0x5f, 0x57, 0x4f],
+        [0xc2, 0xc3, 0xc7],
+        [0xff, 0xf1, 0xe8],
+        [0xff, 0x00, 0x4d],
+        [0xff, 0xa3, 0x00],
+        [0xff, 0xec, 0x27],
+        [0x00, 0xe4, 0x36],
+        [0x29, 0xad, 0xff],
+        [0x83, 0x76, 0x9c],
+        [0xff, 0x77, 0xa8],
+        [0xff, 0xcc, 0xaa],
+    ],
+};
+
+/// The NES (2C02) palette as commonly emulated, without its repeated
+/// blacks.
+pub const NES: NamedPalette = NamedPalette {
+    name: "nes",
+    colors: &[
+        [0x7c, 0x7c, 0x7c],
+        [0x00, 0x00, 0xfc],
+        [0x00, 0x00, 0xbc],
+        [0x44, 0x28, 0xbc],
+        [0x94, 0x00, 0x84],
+        [0xa8, 0x00, 0x20],
+        [0xa8, 0x10, 0x00],
+        [0x88, 0x14, 0x00],
+        [0x50, 0x30, 0x00],
+        [0x00, 0x78, 0x00],
+        [0x00, 0x68, 0x00],
+        [0x00, 0x58, 0x00],
+        [0x00, 0x40, 0x58],
+        [0x00, 0x00, 0x00],
+        [0xbc, 0xbc, 0xbc],
+        [0x00, 0x78, 0xf8],
+        [0x00, 0x58, 0xf8],
+        [0x68, 0x44, 0xfc],
+        [0xd8, 0x00, 0xcc],
+        [0xe4, 0x00, 0x58],
+        [0xf8, 0x38, 0x00],
+        [0xe4, 0x5c, 0x10],
+        [0xac, 0x7c, 0x00],
+        [0x00, 0xb8, 0x00],
+        [0x00, 0xa8, 0x00],
+        [0x00, 0xa8, 0x44],
+        [0x00, 0x88, 0x88],
+        [0xf8, 0xf8, 0xf8],
+        [0x3c, 0xbc, 0xfc],
+        [0x68, 0x88, 0xfc],
+        [0x98, 0x78, 0xf8],
+        [0xf8, 0x78, 0xf8],
+        [0xf8, 0x58, 0x98],
+        [0xf8, 0x78, 0x58],
+        [0xfc, 0xa0, 0x44],
+        [0xf8, 0xb8, 0x00],
+        [0xb8, 0xf8, 0x18],
+        [0x58, 0xd8, 0x54],
+        [0x58, 0xf8, 0x98],
+        [0x00, 0xe8, 0xd8],
+        [0x78, 0x78, 0x78],
+        [0xfc, 0xfc, 0xfc],
+        [0xa4, 0xe4, 0xfc],
+        [0xb8, 0xb8, 0xf8],
+        [0xd8, 0xb8, 0xf8],
+        [0xf8, 0xb8, 0xf8],
+        [0xf8, 0xa4, 0xc0],
+        [0xf0, 0xd0, 0xb0],
+        [0xfc, 0xe0, 0xa8],
+        [0xf8, 0xd8, 0x78],
+        [0xd8, 0xf8, 0x78],
+        [0xb8, 0xf8, 0xb8],
+        [0xb8, 0xf8, 0xd8],
+        [0x00, 0xfc, 0xfc],
+        [0xf8, 0xd8, 0xf8],
+    ],
+};
+
+/// The Commodore 64 palette as listed by the C64 Wiki.
+pub const C64: NamedPalette = NamedPalette {
+    name: "c64",
+    colors: &[
+        [0x00, 0x00, 0x00],
+        [0xff, 0xff, 0xff],
+        [0x88, 0x00, 0x00],
+        [0xaa, 0xff, 0xee],
+        [0xcc, 0x44, 0xcc],
+        [0x00, 0xcc, 0x55],
+        [0x00, 0x00, 0xaa],
+        [0xee, 0xee, 0x77],
+        [0xdd, 0x88, 0x55],
+        [0x66, 0x44, 0x00],
+        [0xff, 0x77, 0x77],
+        [0x33, 0x33, 0x33],
+        [0x77, 0x77, 0x77],
+        [0xaa, 0xff, 0x66],
+        [0x00, 0x88, 0xff],
+        [0xbb, 0xbb, 0xbb],
+    ],
+};
+
+/// The ZX Spectrum's normal and bright colors; bright black is black, so
+/// there are 15.
+pub const ZX_SPECTRUM: NamedPalette = NamedPalette {
+    name: "zx-spectrum",
+    colors: &[
+        [0x00, 0x00, 0x00],
+        [0x00, 0x00, 0xd7],
+        [0xd7, 0x00, 0x00],
+        [0xd7, 0x00, 0xd7],
+        [0x00, 0xd7, 0x00],
+        [0x00, 0xd7, 0xd7],
+        [0xd7, 0xd7, 0x00],
+        [0xd7, 0xd7, 0xd7],
+        [0x00, 0x00, 0xff],
+        [0xff, 0x00, 0x00],
+        [0xff, 0x00, 0xff],
+        [0x00, 0xff, 0x00],
+        [0x00, 0xff, 0xff],
+        [0xff, 0xff, 0x00],
+        [0xff, 0xff, 0xff],
+    ],
+};
+
+const fn web_safe() -> [[u8; 3]; 216] {
+    let mut colors: [[u8; 3]; 216] = [[0; 3]; 216];
+    let mut i: usize = 0;
+    while i < 216 {
+        colors[i] = [
+            (i / 36 * 51) as u8,
+            (i / 6 % 6 * 51) as u8,
+            (i % 6 * 51) as u8,
+        ];
+        i += 1;
+    }
+    colors
+}
+
+/// The 216 web-safe colors: every channel one of `00`, `33`, ..., `ff`.
+pub const WEB_SAFE: NamedPalette = NamedPalette {
+    name: "web-safe",
+    colors: &web_safe(),
+};
+
+pub const PALETTES: [&NamedPalette; 8] = [
+    &GAME_BOY,
+    &CGA,
+    &EGA,
+    &PICO_8,
+    &NES,
+    &C64,
+    &ZX_SPECTRUM,
+    &WEB_SAFE,
+];
+
+impl NamedPalette {
+    /// Looks a palette up by name, ignoring case and `-`/`_`/space
+    /// separators, so `GameBoy`, `pico8` and `zx_spectrum` all work.
+    ///
+    /// Besides the names in [`PALETTES`], `gb`, `zx` and `web` are accepted.
+    pub fn by_name(name: &str) -> Option<&'static NamedPalette> {
+        match normalize_name(name).as_str() {
+            "gb" => Some(&GAME_BOY),
+            "zx" => Some(&ZX_SPECTRUM),
+            "web" => Some(&WEB_SAFE),
+            norm => PALETTES
+                .into_iter()
+                .find(|palette| normalize_name(palette.name) == norm),
+        }
+    }
+
+    /// The colors in the form the dithering functions take.
+    pub fn to_vec(&self) -> Vec<[u8; 3]> {
+        self.colors.to_vec()
+    }
+
+    /// The colors as concatenated hex digits, which [`crate::str_to_pal`]
+    /// parses back into [`NamedPalette::to_vec`].
+    pub fn to_hex(&self) -> String {
+        self.colors
+            .iter()
+            .map(|c| format!("{:02x}{:02x}{:02x}", c[0], c[1], c[2]))
+            .collect()
+    }
+}
@@ -0,0 +1,156 @@
+use image::{ImageBuffer, Pixel};
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+#[cfg(all(test, feature = "parallel"))]
+use std::cell::Cell;
+
+#[cfg(all(test, feature = "parallel"))]
+thread_local! {
+    static SERIAL: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Whether the helpers below use rayon: always with the `parallel` feature,
+/// except inside [`serially`] in tests.
+#[cfg(feature = "parallel")]
+fn parallel() -> bool {
+    #[cfg(test)]
+    return !SERIAL.with(Cell::get);
+    #[cfg(not(test))]
+    true
+}
+
+/// Runs `f` with the helpers of this thread on their serial path, to
+/// compare the two.
+#[cfg(all(test, feature = "parallel"))]
+pub(crate) fn serially<R>(f: impl FnOnce() -> R) -> R {
+    SERIAL.with(|serial| serial.set(true));
+    let out: R = f();
+    SERIAL.with(|serial| serial.set(false));
+    out
+}
+
+/// Calls `f` on every pixel of `img`.
+///
+/// With the `parallel` feature the rows are spread over rayon's thread
+/// pool. Each pixel is still computed by the same closure, so the output is
+/// identical to the serial loop.
+pub(crate) fn for_each_pixel<P, F>(img: &mut ImageBuffer<P, Vec<P::Subpixel>>, f: F)
+where
+    P: Pixel + Send + Sync,
+    P::Subpixel: Send + Sync,
+    F: Fn(&mut P) + Send + Sync,
+{
+    #[cfg(feature = "parallel")]
+    if parallel() {
+        let channels: usize = P::CHANNEL_COUNT as usize;
+        let row: usize = (img.width() as usize * channels).max(1);
+        let buf: &mut [P::Subpixel] = img;
+        buf.par_chunks_mut(row).for_each(|row| {
+            row.chunks_exact_mut(channels)
+                .for_each(|pixel| f(P::from_slice_mut(pixel)))
+        });
+        return;
+    }
+    img.pixels_mut().for_each(f);
+}
+
+/// Builds a `width` by `height` image whose pixel `(x, y)` is `f(x, y)`,
+/// row-parallel like [`for_each_pixel`].
+pub(crate) fn from_fn<P, F>(width: u32, height: u32, f: F) -> ImageBuffer<P, Vec<P::Subpixel>>
+where
+    P: Pixel + Send + Sync,
+    P::Subpixel: Send + Sync,
+    F: Fn(u32, u32) -> P + Send + Sync,
+{
+    #[cfg(feature = "parallel")]
+    if parallel() {
+        let mut img: ImageBuffer<P, Vec<P::Subpixel>> = ImageBuffer::new(width, height);
+        let channels: usize = P::CHANNEL_COUNT as usize;
+        let row: usize = (width as usize * channels).max(1);
+        let buf: &mut [P::Subpixel] = &mut img;
+        buf.par_chunks_mut(row).enumerate().for_each(|(y, row)| {
+            for (x, pixel) in row.chunks_exact_mut(channels).enumerate() {
+                *P::from_slice_mut(pixel) = f(x as u32, y as u32);
+            }
+        });
+        return img;
+    }
+    ImageBuffer::from_fn(width, height, f)
+}
+
+/// `f(y)` for every row `y` in `0..height`, computed in parallel like
+/// [`for_each_pixel`].
+pub(crate) fn rows<R, F>(height: u32, f: F) -> Vec<R>
+where
+    R: Send,
+    F: Fn(u32) -> R + Send + Sync,
+{
+    #[cfg(feature = "parallel")]
+    if parallel() {
+        return (0..height).into_par_iter().map(f).collect();
+    }
+    (0..height).map(f).collect()
+}
+
+#[cfg(all(test, feature = "parallel"))]
+mod tests {
+    use super::serially;
+    use crate::rng::SplitMix64;
+    use crate::*;
+    use image::{Rgb, RgbImage, Rgba, RgbaImage};
+
+    /// Random pixels on a non-square image, so swapped axes show up.
+    fn noise() -> RgbImage {
+        let mut rng = SplitMix64::new(17);
+        RgbImage::from_fn(37, 23, |_, _| Rgb([0; 3].map(|_: u8| rng.below(256) as u8)))
+    }
+
+    fn same<F: Fn(&mut RgbImage)>(name: &str, f: F) {
+        let (mut parallel, mut serial) = (noise(), noise());
+        f(&mut parallel);
+        serially(|| f(&mut serial));
+        assert!(parallel == serial, "{} differs from the serial path", name);
+    }
+
+    #[test]
+    fn filters_match_the_serial_path() {
+        same("bright", |img| bright(img, 1.3));
+        same("colorize", |img| colorize(img, Rgb([200, 120, 40])));
+        same("add", |img| add(img, Rgb([30, 0, 90])));
+        same("pinkize", pinkize);
+        same("to_n_val_channels", |img| to_n_val_channels(img, 4));
+        same("edit_color", |img| {
+            let colors = vec![Rgb([128, 128, 128]), Rgb([255, 0, 0])];
+            edit_color(
+                img,
+                colors,
+                ColorTolerance::Metric(ColorMetric::Euclidean, 120.0),
+            )
+        });
+        same("upscale", |img| *img = upscale(img.clone(), 3).unwrap());
+        same("levels", |img| levels(img, 0.1, 0.9, 1.4));
+        same("hsl_adjust", |img| hsl_adjust(img, 40.0, 1.2, 0.05));
+        same("gradient_map", |img| {
+            gradient_map(img, &Gradient::parse("fire").unwrap())
+        });
+        same("resize", |img| {
+            *img = resize(img, 50, 17, ResampleFilter::Lanczos3, false)
+        });
+    }
+
+    #[test]
+    fn pixel_art_matches_the_serial_path() {
+        let rgba = |img: RgbImage| -> RgbaImage {
+            RgbaImage::from_fn(img.width(), img.height(), |x, y| {
+                let [r, g, b] = img.get_pixel(x, y).0;
+                Rgba([r / 64 * 64, g / 64 * 64, b / 64 * 64, 255])
+            })
+        };
+        let img = rgba(noise());
+        for k in [2, 3] {
+            assert!(hqx(&img, k).unwrap() == serially(|| hqx(&img, k).unwrap()));
+            assert!(xbr(&img, k).unwrap() == serially(|| xbr(&img, k).unwrap()));
+        }
+    }
+}
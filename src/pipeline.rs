@@ -0,0 +1,206 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+use image::DynamicImage;
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+use crate::filter::{self, Filter, Params};
+
+/// A parameter of a [`Step`]. Values are read the way the command line
+/// reads `key=value` arguments, so `k = 4` and `k = "4"` mean the same.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ParamValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Str(String),
+}
+
+impl fmt::Display for ParamValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParamValue::Bool(val) => write!(f, "{}", val),
+            ParamValue::Int(val) => write!(f, "{}", val),
+            ParamValue::Float(val) => write!(f, "{}", val),
+            ParamValue::Str(val) => write!(f, "{}", val),
+        }
+    }
+}
+
+impl From<bool> for ParamValue {
+    fn from(val: bool) -> Self {
+        ParamValue::Bool(val)
+    }
+}
+
+impl From<i64> for ParamValue {
+    fn from(val: i64) -> Self {
+        ParamValue::Int(val)
+    }
+}
+
+impl From<u32> for ParamValue {
+    fn from(val: u32) -> Self {
+        ParamValue::Int(val as i64)
+    }
+}
+
+impl From<f64> for ParamValue {
+    fn from(val: f64) -> Self {
+        ParamValue::Float(val)
+    }
+}
+
+impl From<f32> for ParamValue {
+    fn from(val: f32) -> Self {
+        ParamValue::Float(val as f64)
+    }
+}
+
+impl From<&str> for ParamValue {
+    fn from(val: &str) -> Self {
+        ParamValue::Str(val.to_string())
+    }
+}
+
+impl From<String> for ParamValue {
+    fn from(val: String) -> Self {
+        ParamValue::Str(val)
+    }
+}
+
+/// One operation of a [`Pipeline`] with its parameters: the name and
+/// parameters of one of the [`filter::FILTERS`], e.g. `bayer` with `pal`,
+/// `map` and `pixel_size`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Step {
+    pub op: String,
+    #[serde(flatten)]
+    pub params: BTreeMap<String, ParamValue>,
+}
+
+impl Step {
+    pub fn new(op: &str) -> Step {
+        Step {
+            op: op.to_string(),
+            params: BTreeMap::new(),
+        }
+    }
+
+    /// Sets parameter `key`, replacing an earlier value.
+    pub fn param(mut self, key: &str, val: impl Into<ParamValue>) -> Step {
+        self.params.insert(key.to_string(), val.into());
+        self
+    }
+}
+
+/// An ordered list of operations that can be saved as a TOML or JSON recipe
+/// and replayed on any image.
+///
+/// In TOML every step is a `[[steps]]` table:
+///
+/// ```toml
+/// [[steps]]
+/// op = "downscale"
+/// k = 4
+///
+/// [[steps]]
+/// op = "bayer"
+/// pal = "pico-8"
+/// map = "bayer4"
+///
+/// [[steps]]
+/// op = "upscale"
+/// k = 4
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Pipeline {
+    #[serde(default)]
+    pub steps: Vec<Step>,
+}
+
+impl Pipeline {
+    pub fn new() -> Pipeline {
+        Pipeline::default()
+    }
+
+    /// Appends `step`.
+    pub fn then(mut self, step: Step) -> Pipeline {
+        self.steps.push(step);
+        self
+    }
+
+    pub fn from_toml(text: &str) -> Result<Pipeline, Error> {
+        toml::from_str(text).map_err(recipe_error)
+    }
+
+    pub fn to_toml(&self) -> Result<String, Error> {
+        toml::to_string(self).map_err(recipe_error)
+    }
+
+    pub fn from_json(text: &str) -> Result<Pipeline, Error> {
+        serde_json::from_str(text).map_err(recipe_error)
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        serde_json::to_string_pretty(self).map_err(recipe_error)
+    }
+
+    /// Reads a recipe, as JSON for a `.json` file and as TOML otherwise.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Pipeline, Error> {
+        let text = std::fs::read_to_string(&path)?;
+        if is_json(path.as_ref()) {
+            Pipeline::from_json(&text)
+        } else {
+            Pipeline::from_toml(&text)
+        }
+    }
+
+    /// Writes the recipe, as JSON for a `.json` file and as TOML otherwise.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let text = if is_json(path.as_ref()) {
+            self.to_json()?
+        } else {
+            self.to_toml()?
+        };
+        Ok(std::fs::write(path, text)?)
+    }
+
+    /// Runs the steps on `img` in order, each with the [`Filter`] of its
+    /// `op`. The image keeps its depth from step to step, see
+    /// [`Filter`], so open it with [`crate::open_img_dynamic`] to keep the
+    /// precision of 16-bit and float files.
+    pub fn run(&self, mut img: DynamicImage) -> Result<DynamicImage, Error> {
+        for (index, step) in self.steps.iter().enumerate() {
+            img = run_step(step, img).map_err(|err| Error::Step {
+                index,
+                op: step.op.clone(),
+                source: Box::new(err),
+            })?;
+        }
+        Ok(img)
+    }
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+}
+
+/// The recipe is not valid TOML or JSON, or does not describe a pipeline.
+fn recipe_error(err: impl fmt::Display) -> Error {
+    Error::InvalidParameter(format!("invalid recipe: {}", err))
+}
+
+fn run_step(step: &Step, img: DynamicImage) -> Result<DynamicImage, Error> {
+    let filter: &dyn Filter = filter::by_name(&step.op)
+        .ok_or_else(|| Error::InvalidParameter(format!("unknown operation `{}`", step.op)))?;
+    let values = step
+        .params
+        .iter()
+        .map(|(key, val)| (key.clone(), val.to_string()));
+    filter.apply(img, &Params::new(filter, values)?)
+}
@@ -0,0 +1,854 @@
+use crate::depth::Channel;
+use crate::error::Error;
+use crate::par;
+use image::{ImageBuffer, Pixel};
+
+/// The neighbours of one source pixel, looked at from one of its corners.
+///
+/// Offsets and block cells are written for the bottom-right corner, so
+/// `at(1, 0)` is the right neighbour and `at(1, 1)` the diagonal one; each
+/// quarter turn moves the corner clockwise. Lookups past the border repeat
+/// the edge pixels.
+#[derive(Clone, Copy)]
+struct Window<'a, P: Pixel> {
+    img: &'a ImageBuffer<P, Vec<P::Subpixel>>,
+    x: u32,
+    y: u32,
+    quarters: u8,
+}
+
+impl<P: Pixel> Window<'_, P> {
+    fn turned(self, quarters: u8) -> Self {
+        Window { quarters, ..self }
+    }
+
+    fn at(&self, dx: i32, dy: i32) -> P {
+        let (mut dx, mut dy) = (dx, dy);
+        for _ in 0..self.quarters {
+            (dx, dy) = (-dy, dx);
+        }
+        let x: i64 = (self.x as i64 + dx as i64).clamp(0, self.img.width() as i64 - 1);
+        let y: i64 = (self.y as i64 + dy as i64).clamp(0, self.img.height() as i64 - 1);
+        *self.img.get_pixel(x as u32, y as u32)
+    }
+
+    /// Index of cell `(col, row)` of a `k` by `k` block stored row by row.
+    fn cell(&self, k: usize, col: usize, row: usize) -> usize {
+        let (mut col, mut row) = (col, row);
+        for _ in 0..self.quarters {
+            (col, row) = (k - 1 - row, col);
+        }
+        row * k + col
+    }
+}
+
+/// Enlarges `img` by `k`, filling the `k` by `k` block of every pixel with
+/// `block`. The block starts out as copies of the pixel, like [`upscale`].
+/// An enlarged size past `u32::MAX` is an [`Error::InvalidParameter`].
+///
+/// [`upscale`]: crate::upscale
+fn by_blocks<P, F>(
+    img: &ImageBuffer<P, Vec<P::Subpixel>>,
+    k: u32,
+    block: F,
+) -> Result<ImageBuffer<P, Vec<P::Subpixel>>, Error>
+where
+    P: Pixel + Send + Sync,
+    P::Subpixel: Send + Sync,
+    F: Fn(Window<P>, &mut [P]) + Send + Sync,
+{
+    let (width, height) = img.dimensions();
+    let (new_width, new_height) = match (width.checked_mul(k), height.checked_mul(k)) {
+        (Some(new_width), Some(new_height)) => (new_width, new_height),
+        _ => {
+            return Err(Error::InvalidParameter(format!(
+                "cannot scale {}x{} by {}, the result is too large",
+                width, height, k
+            )))
+        }
+    };
+    let size: usize = k as usize;
+    let bands: Vec<Vec<P::Subpixel>> = par::rows(height, |y| {
+        let mut band: Vec<P> = Vec::with_capacity(width as usize * size * size);
+        let mut cells: Vec<P> = Vec::with_capacity(size * size);
+        for x in 0..width {
+            cells.clear();
+            cells.resize(size * size, *img.get_pixel(x, y));
+            let window = Window {
+                img,
+                x,
+                y,
+                quarters: 0,
+            };
+            block(window, &mut cells);
+            band.extend_from_slice(&cells);
+        }
+        // `band` holds the blocks one after the other; reorder them into
+        // `k` rows of output pixels.
+        let mut out: Vec<P::Subpixel> = Vec::with_capacity(band.len() * P::CHANNEL_COUNT as usize);
+        for row in 0..size {
+            for x in 0..width as usize {
+                for col in 0..size {
+                    out.extend_from_slice(band[(x * size + row) * size + col].channels());
+                }
+            }
+        }
+        out
+    });
+    Ok(ImageBuffer::from_raw(new_width, new_height, bands.concat())
+        .expect("bands cover the enlarged image"))
+}
+
+fn unsupported(k: u32, factors: &str) -> Error {
+    Error::InvalidParameter(format!("cannot scale by {}, only by {}", k, factors))
+}
+
+/// The Scale2x corner rule: the corner takes the color of its two edge
+/// neighbours when they agree and the other two do not.
+fn scale2x_block<P: Pixel + PartialEq>(window: Window<P>, cells: &mut [P]) {
+    for quarters in 0..4 {
+        let w = window.turned(quarters);
+        let (b, d, f, h) = (w.at(0, -1), w.at(-1, 0), w.at(1, 0), w.at(0, 1));
+        if h == f && h != d && f != b {
+            cells[w.cell(2, 1, 1)] = f;
+        }
+    }
+}
+
+/// Scale3x: the Scale2x rule for the corners, and edge cells that continue
+/// a diagonal through the pixel.
+fn scale3x_block<P: Pixel + PartialEq>(window: Window<P>, cells: &mut [P]) {
+    let e: P = window.at(0, 0);
+    for quarters in 0..4 {
+        let w = window.turned(quarters);
+        let (b, d, f, h) = (w.at(0, -1), w.at(-1, 0), w.at(1, 0), w.at(0, 1));
+        let (g, i) = (w.at(-1, 1), w.at(1, 1));
+        if h == f && h != d && f != b {
+            cells[w.cell(3, 2, 2)] = f;
+        }
+        if (d == h && d != b && h != f && e != i) || (h == f && d != h && b != f && e != g) {
+            cells[w.cell(3, 1, 2)] = h;
+        }
+    }
+}
+
+/// Scale2x, Scale3x or Scale4x (Scale2x twice) for `k` of 2, 3 or 4.
+///
+/// Each pixel grows into a `k` by `k` block whose corners take the color of
+/// matching neighbours, rounding off staircases without mixing any new
+/// colors, so a dithered palette stays intact. Other factors, and images
+/// too large to enlarge by `k`, are an [`Error::InvalidParameter`].
+pub fn scale_nx<P>(
+    img: &ImageBuffer<P, Vec<P::Subpixel>>,
+    k: u32,
+) -> Result<ImageBuffer<P, Vec<P::Subpixel>>, Error>
+where
+    P: Pixel + PartialEq + Send + Sync,
+    P::Subpixel: Send + Sync,
+{
+    match k {
+        2 => by_blocks(img, 2, scale2x_block),
+        3 => by_blocks(img, 3, scale3x_block),
+        4 => by_blocks(&by_blocks(img, 2, scale2x_block)?, 2, scale2x_block),
+        k => Err(unsupported(k, "2, 3 or 4")),
+    }
+}
+
+/// EPX: a corner takes the color of its two edge neighbours when they are
+/// equal, unless three of the four neighbours are.
+fn epx_block<P: Pixel + PartialEq>(window: Window<P>, cells: &mut [P]) {
+    let around: [P; 4] = [
+        window.at(0, -1),
+        window.at(1, 0),
+        window.at(0, 1),
+        window.at(-1, 0),
+    ];
+    if around
+        .iter()
+        .any(|p| around.iter().filter(|q| *q == p).count() >= 3)
+    {
+        return;
+    }
+    for quarters in 0..4 {
+        let w = window.turned(quarters);
+        let (f, h) = (w.at(1, 0), w.at(0, 1));
+        if f == h {
+            cells[w.cell(2, 1, 1)] = h;
+        }
+    }
+}
+
+/// Eric's Pixel Expansion for `k` of 2, or 4 by running it twice; other
+/// factors, and images too large to enlarge by `k`, are an
+/// [`Error::InvalidParameter`].
+///
+/// The rules are stated differently, but the output is the same as
+/// [`scale_nx`], which rediscovered it.
+pub fn epx<P>(
+    img: &ImageBuffer<P, Vec<P::Subpixel>>,
+    k: u32,
+) -> Result<ImageBuffer<P, Vec<P::Subpixel>>, Error>
+where
+    P: Pixel + PartialEq + Send + Sync,
+    P::Subpixel: Send + Sync,
+{
+    match k {
+        2 => by_blocks(img, 2, epx_block),
+        4 => by_blocks(&by_blocks(img, 2, epx_block)?, 2, epx_block),
+        k => Err(unsupported(k, "2 or 4")),
+    }
+}
+
+/// Channels in `0.0..=1.0` as Y, U, V and alpha.
+fn yuva<P, T>(pixel: &P) -> [f32; 4]
+where
+    P: Pixel<Subpixel = T>,
+    T: Channel,
+{
+    let [r, g, b, a] = pixel.to_rgba().0.map(|v| v.value() / <T as Channel>::MAX);
+    [
+        0.299 * r + 0.587 * g + 0.114 * b,
+        -0.169 * r - 0.331 * g + 0.5 * b,
+        0.5 * r - 0.419 * g - 0.081 * b,
+        a,
+    ]
+}
+
+/// `a` moved towards `b` by `t`.
+fn mix<P, T>(a: P, b: P, t: f32) -> P
+where
+    P: Pixel<Subpixel = T>,
+    T: Channel,
+{
+    a.map2(&b, |x, y| {
+        T::from_value_rounded(x.value() + (y.value() - x.value()) * t)
+    })
+}
+
+/// The hqx similarity test: luma within 48, chroma within 7 and 6 and
+/// alpha within 48 of 255.
+fn hq_similar(a: &[f32; 4], b: &[f32; 4]) -> bool {
+    const LIMITS: [f32; 4] = [48.0 / 255.0, 7.0 / 255.0, 6.0 / 255.0, 48.0 / 255.0];
+    (0..4).all(|c| (a[c] - b[c]).abs() <= LIMITS[c])
+}
+
+/// Share of the square at `(u, v)` with side `side`, in pixel units, that
+/// lies beyond the line `u + v = 1.5` cutting the bottom-right corner.
+fn corner_coverage(u: f32, v: f32, side: f32) -> f32 {
+    let s: f32 = ((u + v + 2.0 * side - 1.5) / side).clamp(0.0, 2.0);
+    if s <= 1.0 {
+        s * s / 2.0
+    } else {
+        1.0 - (2.0 - s) * (2.0 - s) / 2.0
+    }
+}
+
+fn hqx_block<P, T>(window: Window<P>, cells: &mut [P], k: usize)
+where
+    P: Pixel<Subpixel = T>,
+    T: Channel,
+{
+    let e: P = window.at(0, 0);
+    let ye: [f32; 4] = yuva(&e);
+    for quarters in 0..4 {
+        let w = window.turned(quarters);
+        let (f, h, i) = (w.at(1, 0), w.at(0, 1), w.at(1, 1));
+        let (yf, yh) = (yuva(&f), yuva(&h));
+        if hq_similar(&ye, &yf) || hq_similar(&ye, &yh) || !hq_similar(&yf, &yh) {
+            continue;
+        }
+        // A lone diagonal line through the corner is only softened, so thin
+        // lines keep their weight.
+        let strength: f32 = if hq_similar(&ye, &yuva(&i)) { 0.5 } else { 1.0 };
+        let edge: P = mix(f, h, 0.5);
+        let side: f32 = 1.0 / k as f32;
+        for row in 0..k {
+            for col in 0..k {
+                let cover: f32 = corner_coverage(col as f32 * side, row as f32 * side, side);
+                if cover > 0.0 {
+                    let at: usize = w.cell(k, col, row);
+                    cells[at] = mix(cells[at], edge, cover * strength);
+                }
+            }
+        }
+    }
+}
+
+/// hq2x, hq3x or hq4x-style smoothing for `k` of 2, 3 or 4; other factors,
+/// and images too large to enlarge by `k`, are an
+/// [`Error::InvalidParameter`].
+///
+/// Neighbours are compared in YUV with hqx's thresholds. Where the two
+/// neighbours at a corner match each other but not the pixel, an edge runs
+/// across it and the corner is cut off at 45 degrees, every cell blended by
+/// how much of it the cut covers. This reproduces hqx's blend ratios for
+/// plain edges (1:1:2 at 2x, 7:1 and 1:1 at 3x) from geometry instead of
+/// its 256-case lookup tables, so the output is close to but not bit for
+/// bit that of the original.
+pub fn hqx<P, T>(img: &ImageBuffer<P, Vec<T>>, k: u32) -> Result<ImageBuffer<P, Vec<T>>, Error>
+where
+    P: Pixel<Subpixel = T> + Send + Sync,
+    T: Channel,
+{
+    match k {
+        2..=4 => by_blocks(img, k, |window, cells| hqx_block(window, cells, k as usize)),
+        k => Err(unsupported(k, "2, 3 or 4")),
+    }
+}
+
+/// The weighted YUV distance of xBR.
+fn xbr_dist(a: &[f32; 4], b: &[f32; 4]) -> f32 {
+    const WEIGHTS: [f32; 4] = [48.0, 7.0, 6.0, 48.0];
+    (0..4).map(|c| (a[c] - b[c]).abs() * WEIGHTS[c]).sum()
+}
+
+/// A cell of a block at the bottom-right corner, as `(col, row)`.
+type Cell = (usize, usize);
+
+/// How an edge shape changes the cells at the bottom-right corner of a
+/// block: cells blended towards the new color by the given share, then
+/// cells set to it, then cells copied from another one.
+struct Shape {
+    blend: &'static [(Cell, f32)],
+    set: &'static [Cell],
+    copy: &'static [(Cell, Cell)],
+}
+
+/// Shapes by factor, in the order diagonal, shallow (left), steep (up),
+/// shallow and steep, then the fallback for weak edges.
+const XBR_SHAPES: [[Shape; 5]; 3] = [
+    [
+        Shape {
+            blend: &[((1, 1), 0.5)],
+            set: &[],
+            copy: &[],
+        },
+        Shape {
+            blend: &[((1, 1), 0.75), ((0, 1), 0.25)],
+            set: &[],
+            copy: &[],
+        },
+        Shape {
+            blend: &[((1, 1), 0.75), ((1, 0), 0.25)],
+            set: &[],
+            copy: &[],
+        },
+        Shape {
+            blend: &[((1, 1), 0.875), ((0, 1), 0.25)],
+            set: &[],
+            copy: &[((1, 0), (0, 1))],
+        },
+        Shape {
+            blend: &[((1, 1), 0.5)],
+            set: &[],
+            copy: &[],
+        },
+    ],
+    [
+        Shape {
+            blend: &[((2, 2), 0.875), ((2, 1), 0.125), ((1, 2), 0.125)],
+            set: &[],
+            copy: &[],
+        },
+        Shape {
+            blend: &[((1, 2), 0.75), ((2, 1), 0.25), ((0, 2), 0.25)],
+            set: &[(2, 2)],
+            copy: &[],
+        },
+        Shape {
+            blend: &[((2, 1), 0.75), ((1, 2), 0.25), ((2, 0), 0.25)],
+            set: &[(2, 2)],
+            copy: &[],
+        },
+        Shape {
+            blend: &[((1, 2), 0.75), ((0, 2), 0.25)],
+            set: &[(2, 2)],
+            copy: &[((2, 1), (1, 2)), ((2, 0), (0, 2))],
+        },
+        Shape {
+            blend: &[((2, 2), 0.5)],
+            set: &[],
+            copy: &[],
+        },
+    ],
+    [
+        Shape {
+            blend: &[((3, 2), 0.5), ((2, 3), 0.5)],
+            set: &[(3, 3)],
+            copy: &[],
+        },
+        Shape {
+            blend: &[
+                ((3, 2), 0.75),
+                ((1, 3), 0.75),
+                ((2, 2), 0.25),
+                ((0, 3), 0.25),
+            ],
+            set: &[(2, 3), (3, 3)],
+            copy: &[],
+        },
+        Shape {
+            blend: &[
+                ((2, 3), 0.75),
+                ((3, 1), 0.75),
+                ((2, 2), 0.25),
+                ((3, 0), 0.25),
+            ],
+            set: &[(3, 2), (3, 3)],
+            copy: &[],
+        },
+        Shape {
+            blend: &[((1, 3), 0.75), ((0, 3), 0.25)],
+            set: &[(3, 3), (2, 3), (3, 2)],
+            copy: &[((2, 2), (0, 3)), ((3, 0), (0, 3)), ((3, 1), (1, 3))],
+        },
+        Shape {
+            blend: &[((3, 3), 0.5)],
+            set: &[],
+            copy: &[],
+        },
+    ],
+];
+
+fn xbr_block<P, T>(window: Window<P>, cells: &mut [P], k: usize)
+where
+    P: Pixel<Subpixel = T> + PartialEq,
+    T: Channel,
+{
+    const SIMILAR: f32 = 15.0;
+    for quarters in 0..4 {
+        let w = window.turned(quarters);
+        let px = |dx: i32, dy: i32| w.at(dx, dy);
+        let (e, f, h) = (px(0, 0), px(1, 0), px(0, 1));
+        if e == f || e == h {
+            continue;
+        }
+        let [ye, yf, yh, yi] = [e, f, h, px(1, 1)].map(|p| yuva(&p));
+        let [yb, yc, yd, yg] = [px(0, -1), px(1, -1), px(-1, 0), px(-1, 1)].map(|p| yuva(&p));
+        let [yf4, yi4, yh5, yi5] = [px(2, 0), px(2, 1), px(0, 2), px(1, 2)].map(|p| yuva(&p));
+        let d = xbr_dist;
+        let eq = |a: &[f32; 4], b: &[f32; 4]| d(a, b) < SIMILAR;
+
+        // Edge strength along the corner against across it.
+        let along: f32 =
+            d(&ye, &yc) + d(&ye, &yg) + d(&yi, &yh5) + d(&yi, &yf4) + 4.0 * d(&yh, &yf);
+        let across: f32 =
+            d(&yh, &yd) + d(&yh, &yi5) + d(&yf, &yi4) + d(&yf, &yb) + 4.0 * d(&ye, &yi);
+        if along > across {
+            continue;
+        }
+        let new: P = if d(&ye, &yf) <= d(&ye, &yh) { f } else { h };
+        let sharp: bool = along < across
+            && ((!eq(&yf, &yb) && !eq(&yh, &yd))
+                || (eq(&ye, &yi) && !eq(&yf, &yi4) && !eq(&yh, &yi5))
+                || eq(&ye, &yg)
+                || eq(&ye, &yc));
+        let shape: usize = if sharp {
+            let (g, c) = (px(-1, 1), px(1, -1));
+            let (ke, ki) = (d(&yf, &yg), d(&yh, &yc));
+            let left: bool = 2.0 * ke <= ki && e != g && px(-1, 0) != g;
+            let up: bool = ke >= 2.0 * ki && e != c && px(0, -1) != c;
+            match (left, up) {
+                (true, true) => 3,
+                (true, false) => 1,
+                (false, true) => 2,
+                (false, false) => 0,
+            }
+        } else {
+            4
+        };
+        let shape: &Shape = &XBR_SHAPES[k - 2][shape];
+        for &((col, row), t) in shape.blend {
+            let at: usize = w.cell(k, col, row);
+            cells[at] = mix(cells[at], new, t);
+        }
+        for &(col, row) in shape.set {
+            cells[w.cell(k, col, row)] = new;
+        }
+        for &((col, row), (from_col, from_row)) in shape.copy {
+            cells[w.cell(k, col, row)] = cells[w.cell(k, from_col, from_row)];
+        }
+    }
+}
+
+/// xBR (level 2) for `k` of 2, 3 or 4; other factors, and images too large
+/// to enlarge by `k`, are an [`Error::InvalidParameter`].
+///
+/// Weighs the color differences along and across every corner over a 5 by
+/// 5 neighbourhood to find edges, including shallow and steep ones, and
+/// blends the corner cells towards the neighbour on the other side. Smoother
+/// than [`hqx`] on curves, at the price of a softer look.
+pub fn xbr<P, T>(img: &ImageBuffer<P, Vec<T>>, k: u32) -> Result<ImageBuffer<P, Vec<T>>, Error>
+where
+    P: Pixel<Subpixel = T> + PartialEq + Send + Sync,
+    T: Channel,
+{
+    match k {
+        2..=4 => by_blocks(img, k, |window, cells| xbr_block(window, cells, k as usize)),
+        k => Err(unsupported(k, "2, 3 or 4")),
+    }
+}
+
+/// The grid of an enlarged sprite: every native pixel became a
+/// `cell_width` by `cell_height` block, the first whole one starting at
+/// `(offset_x, offset_y)`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PixelGrid {
+    pub cell_width: u32,
+    pub cell_height: u32,
+    pub offset_x: u32,
+    pub offset_y: u32,
+}
+
+impl PixelGrid {
+    /// Finds the grid of `img` from where its colors change.
+    ///
+    /// Per axis, this is the largest cell size (and its offset) that puts
+    /// at least three quarters of the color edges on cell borders. Edges
+    /// are changes of more than a tenth of full intensity in any channel,
+    /// so noise and compression artifacts do not count, and a change
+    /// blurred over a few pixels counts once, where it starts. An
+    /// image without a coarser grid gets cells of 1.
+    pub fn detect<P, T>(img: &ImageBuffer<P, Vec<T>>) -> PixelGrid
+    where
+        P: Pixel<Subpixel = T>,
+        T: Channel,
+    {
+        let (width, height) = img.dimensions();
+        let rgba = |x: u32, y: u32| {
+            let pixel = img.get_pixel(x, y).to_rgba().0;
+            pixel.map(|v| v.value() / <T as Channel>::MAX)
+        };
+        let columns: Vec<u32> = edge_counts(height, width, |y, x| rgba(x, y));
+        let rows: Vec<u32> = edge_counts(width, height, rgba);
+        let (cell_width, offset_x) = grid_period(&columns);
+        let (cell_height, offset_y) = grid_period(&rows);
+        PixelGrid {
+            cell_width,
+            cell_height,
+            offset_x,
+            offset_y,
+        }
+    }
+
+    /// The cells along one axis of `len` pixels as ranges, keeping partial
+    /// cells at the borders when at least half of them is visible.
+    fn cells(len: u32, size: u32, offset: u32) -> Vec<std::ops::Range<u32>> {
+        let size: u32 = size.max(1);
+        let offset: u32 = offset % size;
+        let first: i64 = if offset > 0 {
+            offset as i64 - size as i64
+        } else {
+            0
+        };
+        (first..len as i64)
+            .step_by(size as usize)
+            .map(|start| start.max(0) as u32..((start + size as i64) as u32).min(len))
+            .filter(|cell| 2 * cell.len() as u32 >= size)
+            .collect()
+    }
+}
+
+/// The number of edges before each position `0..len`, over `lines` lines
+/// whose pixels `at(line, position)` returns.
+fn edge_counts<F>(lines: u32, len: u32, at: F) -> Vec<u32>
+where
+    F: Fn(u32, u32) -> [f32; 4],
+{
+    let mut counts: Vec<u32> = vec![0; len as usize];
+    let mut steps: Vec<f32> = vec![0.0; len as usize];
+    for line in 0..lines {
+        // `steps[i]` is the largest channel change from `i - 1` to `i`.
+        for i in 1..len {
+            let (a, b) = (at(line, i - 1), at(line, i));
+            steps[i as usize] = (0..4).map(|c| (a[c] - b[c]).abs()).fold(0.0, f32::max);
+        }
+        for i in 1..len as usize {
+            if steps[i] > 0.1 && steps[i - 1] <= 0.1 {
+                counts[i] += 1;
+            }
+        }
+    }
+    counts
+}
+
+/// The cell size and offset whose borders catch at least three quarters of
+/// the `edges` counted before each position. The edges have to lie on two
+/// borders at least, since a single line fits any cell size.
+fn grid_period(edges: &[u32]) -> (u32, u32) {
+    let total: u64 = edges.iter().map(|&n| n as u64).sum();
+    if total == 0 {
+        return (1, 0);
+    }
+    for size in (2..=edges.len() / 2).rev() {
+        let mut on_border: Vec<u64> = vec![0; size];
+        for (i, &n) in edges.iter().enumerate() {
+            on_border[i % size] += n as u64;
+        }
+        let (offset, best) = on_border
+            .iter()
+            .enumerate()
+            .max_by_key(|&(offset, &n)| (n, std::cmp::Reverse(offset)))
+            .expect("size is at least 2");
+        let borders: usize = (offset..edges.len())
+            .step_by(size)
+            .filter(|&i| edges[i] > 0)
+            .count();
+        if 4 * *best >= 3 * total && borders >= 2 {
+            return (size as u32, offset as u32);
+        }
+    }
+    (1, 0)
+}
+
+/// How [`downscale_grid`] picks the color of a cell.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum CellColor {
+    /// The most common color, ties going to the one seen first; keeps the
+    /// palette exact.
+    #[default]
+    Majority,
+    /// The median of each channel, for blurred or compressed sources.
+    Median,
+}
+
+impl CellColor {
+    pub fn by_name(name: &str) -> Option<CellColor> {
+        match name {
+            "majority" => Some(CellColor::Majority),
+            "median" => Some(CellColor::Median),
+            _ => None,
+        }
+    }
+}
+
+/// Shrinks `img` to one pixel per cell of `grid`, colored by `pick`. Use
+/// [`PixelGrid::detect`] to undo an enlargement without knowing it.
+///
+/// Unlike [`downscale`], which keeps every `k`-th pixel from the top left
+/// corner, the cells follow the grid's offset and every pixel of a cell
+/// has its say, so blurred or shifted borders do not leak into the result.
+///
+/// [`downscale`]: crate::downscale
+pub fn downscale_grid<P, T>(
+    img: &ImageBuffer<P, Vec<T>>,
+    grid: &PixelGrid,
+    pick: CellColor,
+) -> ImageBuffer<P, Vec<T>>
+where
+    P: Pixel<Subpixel = T> + PartialEq + Send + Sync,
+    T: Channel,
+{
+    let (width, height) = img.dimensions();
+    let columns = PixelGrid::cells(width, grid.cell_width, grid.offset_x);
+    let rows = PixelGrid::cells(height, grid.cell_height, grid.offset_y);
+    par::from_fn(columns.len() as u32, rows.len() as u32, |x, y| {
+        let (columns, rows) = (columns[x as usize].clone(), rows[y as usize].clone());
+        let cell = rows.flat_map(|y| columns.clone().map(move |x| (x, y)));
+        let pixels = cell.map(|(x, y)| *img.get_pixel(x, y));
+        match pick {
+            CellColor::Majority => {
+                let mut counts: Vec<(P, usize)> = Vec::new();
+                for pixel in pixels {
+                    match counts.iter_mut().find(|(color, _)| *color == pixel) {
+                        Some((_, n)) => *n += 1,
+                        None => counts.push((pixel, 1)),
+                    }
+                }
+                let most: usize = counts.iter().map(|&(_, n)| n).max().unwrap_or(0);
+                counts
+                    .into_iter()
+                    .find(|&(_, n)| n == most)
+                    .map(|(color, _)| color)
+                    .expect("cells are never empty")
+            }
+            CellColor::Median => {
+                let pixels: Vec<P> = pixels.collect();
+                let mut out: P = pixels[0];
+                for (c, channel) in out.channels_mut().iter_mut().enumerate() {
+                    let mut values: Vec<T> = pixels.iter().map(|p| p.channels()[c]).collect();
+                    values.sort_by(|a, b| a.value().total_cmp(&b.value()));
+                    *channel = values[values.len() / 2];
+                }
+                out
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::SplitMix64;
+    use image::imageops::{flip_horizontal, rotate180, rotate270, rotate90};
+    use image::{Rgb, RgbImage};
+
+    const INK: Rgb<u8> = Rgb([20, 20, 120]);
+    const PAPER: Rgb<u8> = Rgb([250, 240, 200]);
+
+    /// A `width` by `height` image of random colors out of `colors`.
+    fn noise(width: u32, height: u32, colors: &[[u8; 3]], seed: u64) -> RgbImage {
+        let mut rng = SplitMix64::new(seed);
+        RgbImage::from_fn(width, height, |_, _| Rgb(colors[rng.below(colors.len())]))
+    }
+
+    fn transpose(img: &RgbImage) -> RgbImage {
+        flip_horizontal(&rotate90(img))
+    }
+
+    #[test]
+    fn scale2x_rounds_off_a_diagonal() {
+        // The AdvanceMAME rules worked through by hand, with the border
+        // pixels repeated.
+        let (x, o) = (INK, PAPER);
+        let img = RgbImage::from_fn(2, 2, |i, j| if i == j { x } else { o });
+        let expected = [[x, x, o, o], [x, o, x, o], [o, x, o, x], [o, o, x, x]];
+        let out = scale_nx(&img, 2).unwrap();
+        for (j, row) in expected.iter().enumerate() {
+            for (i, pixel) in row.iter().enumerate() {
+                assert_eq!(out.get_pixel(i as u32, j as u32), pixel, "({}, {})", i, j);
+            }
+        }
+    }
+
+    #[test]
+    fn epx_matches_scale2x() {
+        let palettes: [&[[u8; 3]]; 2] = [&[[0; 3], [255; 3]], &[[0; 3], [255; 3], [200, 40, 40]]];
+        for (seed, colors) in palettes.iter().enumerate() {
+            let img = noise(13, 9, colors, seed as u64);
+            for k in [2, 4] {
+                assert_eq!(
+                    epx(&img, k).unwrap(),
+                    scale_nx(&img, k).unwrap(),
+                    "k = {}",
+                    k
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn unsupported_factors_are_rejected() {
+        let img = RgbImage::new(2, 2);
+        assert!(scale_nx(&img, 5).is_err());
+        assert!(epx(&img, 3).is_err());
+        assert!(hqx(&img, 1).is_err());
+        assert!(xbr(&img, 5).is_err());
+    }
+
+    #[test]
+    fn xbr_shapes_are_mirror_images() {
+        let mirror = |cells: &[Cell]| -> Vec<Cell> { cells.iter().map(|&(c, r)| (r, c)).collect() };
+        let blended =
+            |shape: &Shape| -> Vec<Cell> { shape.blend.iter().map(|&(at, _)| at).collect() };
+        for (shapes, k) in XBR_SHAPES.iter().zip(2..) {
+            for shape in shapes {
+                let copied = shape.copy.iter().flat_map(|&(to, from)| [to, from]);
+                let cells = blended(shape).into_iter().chain(shape.set.iter().copied());
+                for (col, row) in cells.chain(copied) {
+                    assert!(col < k && row < k, "({}, {}) at {}x", col, row, k);
+                }
+                for &(_, t) in shape.blend {
+                    assert!(t > 0.0 && t < 1.0);
+                }
+            }
+            let [diagonal, shallow, steep, _, weak] = shapes;
+            let shares =
+                |shape: &Shape| -> Vec<f32> { shape.blend.iter().map(|&(_, t)| t).collect() };
+            assert_eq!(mirror(&blended(shallow)), blended(steep), "{}x", k);
+            assert_eq!(shares(shallow), shares(steep), "{}x", k);
+            assert_eq!(mirror(shallow.set), steep.set, "{}x", k);
+            assert!(shallow.copy.is_empty() && steep.copy.is_empty());
+            for shape in [diagonal, weak] {
+                let mut cells = blended(shape);
+                let mut mirrored = mirror(&cells);
+                cells.sort();
+                mirrored.sort();
+                assert_eq!(cells, mirrored, "{}x", k);
+            }
+        }
+    }
+
+    #[test]
+    fn xbr_follows_rotations_and_transposes() {
+        // Staircases of every slope, a convex corner and a lone dot; each
+        // pixel has at most one edge corner, so the order the corners are
+        // visited in does not matter. Between them they use every shape.
+        let shapes: [fn(u32, u32) -> bool; 7] = [
+            |x, y| x + y < 14,
+            |x, y| x + 2 * y < 14,
+            |x, y| 2 * x + y < 14,
+            |x, y| x + 3 * y < 14,
+            |x, y| 3 * x + y < 14,
+            |x, y| x.max(y) < 6,
+            |x, y| (x, y) == (5, 6),
+        ];
+        for (n, inked) in shapes.iter().enumerate() {
+            let img = RgbImage::from_fn(12, 12, |x, y| if inked(x, y) { INK } else { PAPER });
+            for k in 2..=4 {
+                let out = xbr(&img, k).unwrap();
+                assert_ne!(
+                    out,
+                    crate::upscale(img.clone(), k).unwrap(),
+                    "shape {} at {}x",
+                    n,
+                    k
+                );
+                let turns: [fn(&RgbImage) -> RgbImage; 4] =
+                    [rotate90, rotate180, rotate270, transpose];
+                for turn in turns {
+                    assert_eq!(
+                        turn(&out),
+                        xbr(&turn(&img), k).unwrap(),
+                        "shape {} at {}x",
+                        n,
+                        k
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn detect_finds_an_offset_grid_and_downscale_grid_undoes_it() {
+        let colors = [[0, 0, 0], [255, 255, 255], [200, 40, 40]];
+        let sprite = noise(10, 8, &colors, 3);
+        // Cut into the first cells so that one pixel of the first column
+        // and two of the first row are left.
+        let enlarged = crate::upscale(sprite.clone(), 3).unwrap();
+        let img = image::imageops::crop_imm(&enlarged, 2, 1, 28, 23).to_image();
+        let grid = PixelGrid::detect(&img);
+        assert_eq!(
+            grid,
+            PixelGrid {
+                cell_width: 3,
+                cell_height: 3,
+                offset_x: 1,
+                offset_y: 2,
+            }
+        );
+        // The cell cut to a third is dropped, the one cut to two thirds kept.
+        let expected = image::imageops::crop_imm(&sprite, 1, 0, 9, 8).to_image();
+        for pick in [CellColor::Majority, CellColor::Median] {
+            assert_eq!(downscale_grid(&img, &grid, pick), expected, "{:?}", pick);
+        }
+    }
+
+    #[test]
+    fn detect_finds_cells_of_1_without_a_grid() {
+        let no_grid = PixelGrid {
+            cell_width: 1,
+            cell_height: 1,
+            offset_x: 0,
+            offset_y: 0,
+        };
+        let colors = [[0, 0, 0], [255, 255, 255], [200, 40, 40]];
+        let img = noise(30, 20, &colors, 5);
+        assert_eq!(PixelGrid::detect(&img), no_grid);
+        assert_eq!(downscale_grid(&img, &no_grid, CellColor::Majority), img);
+        assert_eq!(PixelGrid::detect(&RgbImage::new(30, 20)), no_grid);
+        // A single edge fits any cell size, so it does not make a grid.
+        let half = RgbImage::from_fn(30, 20, |x, _| if x < 12 { INK } else { PAPER });
+        assert_eq!(PixelGrid::detect(&half), no_grid);
+    }
+}
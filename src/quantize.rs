@@ -0,0 +1,333 @@
+use crate::rng::SplitMix64;
+use image::{ImageBuffer, Rgb};
+use std::cmp;
+use std::collections::HashMap;
+
+/// Distinct colors of `img` with their pixel counts, sorted by color so
+/// results do not depend on hash order.
+fn histogram(img: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> Vec<([u8; 3], u32)> {
+    let mut counts: HashMap<[u8; 3], u32> = HashMap::new();
+    for pixel in img.pixels() {
+        *counts.entry(pixel.0).or_insert(0) += 1;
+    }
+    let mut hist: Vec<([u8; 3], u32)> = counts.into_iter().collect();
+    hist.sort_unstable();
+    hist
+}
+
+fn weighted_mean(colors: &[([u8; 3], u32)]) -> [u8; 3] {
+    let mut sum: [u64; 3] = [0; 3];
+    let mut total: u64 = 0;
+    for &(color, count) in colors {
+        for c in 0..3 {
+            sum[c] += color[c] as u64 * count as u64;
+        }
+        total += count as u64;
+    }
+    [
+        ((sum[0] + total / 2) / total) as u8,
+        ((sum[1] + total / 2) / total) as u8,
+        ((sum[2] + total / 2) / total) as u8,
+    ]
+}
+
+/// Heckbert's median cut: repeatedly splits the color box with the widest
+/// channel range at its pixel-weighted median until there are `n` boxes,
+/// then averages each box.
+pub fn median_cut(img: &ImageBuffer<Rgb<u8>, Vec<u8>>, n: usize) -> Vec<[u8; 3]> {
+    if n == 0 {
+        return vec![];
+    }
+    let hist = histogram(img);
+    if hist.len() <= n {
+        return hist.into_iter().map(|(color, _)| color).collect();
+    }
+    let mut boxes: Vec<Vec<([u8; 3], u32)>> = vec![hist];
+    while boxes.len() < n {
+        let mut widest: Option<(usize, usize, u8)> = None;
+        for (b, colors) in boxes.iter().enumerate() {
+            for c in 0..3 {
+                let min: u8 = colors.iter().map(|(color, _)| color[c]).min().unwrap_or(0);
+                let max: u8 = colors.iter().map(|(color, _)| color[c]).max().unwrap_or(0);
+                if widest.is_none_or(|(_, _, range)| max - min > range) {
+                    widest = Some((b, c, max - min));
+                }
+            }
+        }
+        let (b, c, range) = widest.expect("at least one box");
+        if range == 0 {
+            break;
+        }
+        let colors = &mut boxes[b];
+        colors.sort_by_key(|&(color, _)| color[c]);
+        let half: u64 = colors.iter().map(|&(_, count)| count as u64).sum::<u64>() / 2;
+        let mut acc: u64 = 0;
+        let mut split: usize = 1;
+        for (i, &(_, count)) in colors.iter().enumerate() {
+            acc += count as u64;
+            if acc >= half {
+                split = i + 1;
+                break;
+            }
+        }
+        let split: usize = split.clamp(1, colors.len() - 1);
+        let upper = colors.split_off(split);
+        boxes.push(upper);
+    }
+    boxes.iter().map(|colors| weighted_mean(colors)).collect()
+}
+
+fn dist2(one: [f32; 3], two: [f32; 3]) -> f32 {
+    (one[0] - two[0]).powi(2) + (one[1] - two[1]).powi(2) + (one[2] - two[2]).powi(2)
+}
+
+/// Lloyd's k-means over the image colors, weighted by pixel count and
+/// seeded with k-means++ from `seed`. Runs at most `iterations` rounds.
+pub fn kmeans(
+    img: &ImageBuffer<Rgb<u8>, Vec<u8>>,
+    n: usize,
+    seed: u64,
+    iterations: u32,
+) -> Vec<[u8; 3]> {
+    if n == 0 {
+        return vec![];
+    }
+    let hist = histogram(img);
+    if hist.len() <= n {
+        return hist.into_iter().map(|(color, _)| color).collect();
+    }
+    let points: Vec<[f32; 3]> = hist
+        .iter()
+        .map(|&(color, _)| [color[0] as f32, color[1] as f32, color[2] as f32])
+        .collect();
+    let mut rng = SplitMix64::new(seed);
+
+    let mut centers: Vec<[f32; 3]> = vec![points[rng.below(points.len())]];
+    let mut nearest: Vec<f32> = points.iter().map(|&p| dist2(p, centers[0])).collect();
+    while centers.len() < n {
+        let total: f64 = (0..points.len())
+            .map(|i| (nearest[i] * hist[i].1 as f32) as f64)
+            .sum();
+        let mut target: f64 = (rng.next_u64() >> 11) as f64 / (1u64 << 53) as f64 * total;
+        let mut pick: usize = points.len() - 1;
+        for i in 0..points.len() {
+            target -= (nearest[i] * hist[i].1 as f32) as f64;
+            if target < 0.0 {
+                pick = i;
+                break;
+            }
+        }
+        centers.push(points[pick]);
+        for (i, &p) in points.iter().enumerate() {
+            nearest[i] = nearest[i].min(dist2(p, points[pick]));
+        }
+    }
+
+    let mut labels: Vec<usize> = vec![0; points.len()];
+    for round in 0..iterations {
+        let mut changed: bool = round == 0;
+        for (i, &p) in points.iter().enumerate() {
+            let mut best: usize = 0;
+            for (k, &center) in centers.iter().enumerate() {
+                if dist2(p, center) < dist2(p, centers[best]) {
+                    best = k;
+                }
+            }
+            if labels[i] != best {
+                labels[i] = best;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+        let mut sums: Vec<([f64; 3], f64)> = vec![([0.0; 3], 0.0); n];
+        for (i, &p) in points.iter().enumerate() {
+            let weight: f64 = hist[i].1 as f64;
+            for (sum, v) in sums[labels[i]].0.iter_mut().zip(p) {
+                *sum += v as f64 * weight;
+            }
+            sums[labels[i]].1 += weight;
+        }
+        for (k, (sum, weight)) in sums.into_iter().enumerate() {
+            if weight > 0.0 {
+                centers[k] = [
+                    (sum[0] / weight) as f32,
+                    (sum[1] / weight) as f32,
+                    (sum[2] / weight) as f32,
+                ];
+            }
+        }
+    }
+    centers
+        .iter()
+        .map(|center| {
+            [
+                center[0].round() as u8,
+                center[1].round() as u8,
+                center[2].round() as u8,
+            ]
+        })
+        .collect()
+}
+
+struct OctreeNode {
+    children: [Option<usize>; 8],
+    sum: [u64; 3],
+    count: u64,
+    pixels: u64,
+}
+
+/// Gervautz-Purgathofer octree quantization: colors are inserted into an
+/// 8-level octree whose deepest, least populated nodes are merged into
+/// their parents until `n` colors are left.
+///
+/// The last merge only folds in as many children as needed, so exactly `n`
+/// colors are returned whenever the image has at least that many.
+pub fn octree(img: &ImageBuffer<Rgb<u8>, Vec<u8>>, n: usize) -> Vec<[u8; 3]> {
+    if n == 0 {
+        return vec![];
+    }
+    let hist = histogram(img);
+    if hist.len() <= n {
+        return hist.into_iter().map(|(color, _)| color).collect();
+    }
+    let new_node = || OctreeNode {
+        children: [None; 8],
+        sum: [0; 3],
+        count: 0,
+        pixels: 0,
+    };
+    let mut nodes: Vec<OctreeNode> = vec![new_node()];
+    let mut levels: Vec<Vec<usize>> = vec![vec![]; 8];
+    let mut colors: usize = 0;
+    for &(color, count) in &hist {
+        let mut node: usize = 0;
+        nodes[0].pixels += count as u64;
+        for level in 0..8 {
+            let shift: u32 = 7 - level as u32;
+            let branch: usize = ((((color[0] >> shift) & 1) << 2)
+                | (((color[1] >> shift) & 1) << 1)
+                | ((color[2] >> shift) & 1)) as usize;
+            node = match nodes[node].children[branch] {
+                Some(child) => child,
+                None => {
+                    nodes.push(new_node());
+                    let child: usize = nodes.len() - 1;
+                    nodes[node].children[branch] = Some(child);
+                    if level < 7 {
+                        levels[level + 1].push(child);
+                    }
+                    child
+                }
+            };
+            nodes[node].pixels += count as u64;
+        }
+        for (sum, v) in nodes[node].sum.iter_mut().zip(color) {
+            *sum += v as u64 * count as u64;
+        }
+        nodes[node].count += count as u64;
+        colors += 1;
+    }
+    levels[0].push(0);
+    for level in levels.iter_mut() {
+        level.sort_by_key(|&node| cmp::Reverse(nodes[node].pixels));
+    }
+
+    let mut level: usize = 7;
+    while colors > n {
+        let node: usize = match levels[level].pop() {
+            Some(node) => node,
+            None => {
+                level -= 1;
+                continue;
+            }
+        };
+        let mut children: Vec<usize> = nodes[node].children.iter().flatten().copied().collect();
+        children.sort_by_key(|&child| nodes[child].pixels);
+        let had_color: bool = nodes[node].count > 0;
+        let take: usize = children.len().min(colors - n + 1 - had_color as usize);
+        for &child in &children[..take] {
+            let (sum, count) = (nodes[child].sum, nodes[child].count);
+            for (acc, v) in nodes[node].sum.iter_mut().zip(sum) {
+                *acc += v;
+            }
+            nodes[node].count += count;
+            for slot in nodes[node].children.iter_mut() {
+                if *slot == Some(child) {
+                    *slot = None;
+                }
+            }
+        }
+        colors = colors + !had_color as usize - take;
+    }
+
+    let mut palette: Vec<[u8; 3]> = vec![];
+    let mut stack: Vec<usize> = vec![0];
+    while let Some(node) = stack.pop() {
+        let node = &nodes[node];
+        if node.count > 0 {
+            let count: u64 = node.count;
+            palette.push([
+                ((node.sum[0] + count / 2) / count) as u8,
+                ((node.sum[1] + count / 2) / count) as u8,
+                ((node.sum[2] + count / 2) / count) as u8,
+            ]);
+        }
+        stack.extend(node.children.iter().rev().flatten());
+    }
+    palette
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noise(width: u32, height: u32, seed: u64) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+        let mut rng = SplitMix64::new(seed);
+        ImageBuffer::from_fn(width, height, |_, _| {
+            Rgb([
+                rng.below(256) as u8,
+                rng.below(256) as u8,
+                rng.below(256) as u8,
+            ])
+        })
+    }
+
+    fn sorted(mut colors: Vec<[u8; 3]>) -> Vec<[u8; 3]> {
+        colors.sort_unstable();
+        colors
+    }
+
+    #[test]
+    fn palettes_hold_at_most_n_colors() {
+        let img = noise(32, 32, 7);
+        for n in [1, 2, 5, 16, 64] {
+            assert!(median_cut(&img, n).len() <= n, "median_cut, n={n}");
+            assert!(kmeans(&img, n, 3, 32).len() <= n, "kmeans, n={n}");
+            assert_eq!(octree(&img, n).len(), n, "octree, n={n}");
+        }
+        assert!(median_cut(&img, 0).is_empty());
+        assert!(kmeans(&img, 0, 3, 32).is_empty());
+        assert!(octree(&img, 0).is_empty());
+    }
+
+    #[test]
+    fn few_colors_are_kept_exactly() {
+        let colors: [[u8; 3]; 3] = [[0, 0, 0], [255, 128, 7], [18, 52, 86]];
+        let img = ImageBuffer::from_fn(5, 4, |x, y| Rgb(colors[((x + y) % 3) as usize]));
+        let expected = sorted(colors.to_vec());
+        for n in [3, 4, 256] {
+            assert_eq!(sorted(median_cut(&img, n)), expected, "median_cut, n={n}");
+            assert_eq!(sorted(kmeans(&img, n, 3, 32)), expected, "kmeans, n={n}");
+            assert_eq!(sorted(octree(&img, n)), expected, "octree, n={n}");
+        }
+    }
+
+    #[test]
+    fn kmeans_is_deterministic_per_seed() {
+        let img = noise(24, 24, 11);
+        assert_eq!(kmeans(&img, 8, 42, 32), kmeans(&img, 8, 42, 32));
+        assert_ne!(kmeans(&img, 8, 42, 32), kmeans(&img, 8, 43, 32));
+    }
+}
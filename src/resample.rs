@@ -0,0 +1,319 @@
+use crate::depth::Channel;
+use crate::par;
+use crate::types_n_convs::{linear_to_srgb, srgb_to_linear};
+use image::{ImageBuffer, Pixel};
+use std::f32::consts::PI;
+
+/// Reconstruction filter used by [`resize`].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ResampleFilter {
+    /// The closest source pixel, for hard pixel art edges.
+    Nearest,
+    /// Area averaging; every source pixel counts as much as it is covered.
+    Box,
+    /// Linear interpolation (a tent filter).
+    Bilinear,
+    /// Mitchell-Netravali bicubic (B = C = 1/3), soft without ringing.
+    Mitchell,
+    /// Catmull-Rom bicubic (B = 0, C = 1/2), sharper than Mitchell.
+    CatmullRom,
+    /// Windowed sinc with three lobes, the sharpest of them.
+    #[default]
+    Lanczos3,
+}
+
+impl ResampleFilter {
+    pub const ALL: [ResampleFilter; 6] = [
+        ResampleFilter::Nearest,
+        ResampleFilter::Box,
+        ResampleFilter::Bilinear,
+        ResampleFilter::Mitchell,
+        ResampleFilter::CatmullRom,
+        ResampleFilter::Lanczos3,
+    ];
+
+    /// Other names [`ResampleFilter::by_name`] accepts.
+    pub const ALIASES: [(&'static str, ResampleFilter); 2] = [
+        ("bicubic", ResampleFilter::CatmullRom),
+        ("lanczos", ResampleFilter::Lanczos3),
+    ];
+
+    /// Every name [`ResampleFilter::by_name`] accepts, those of
+    /// [`ResampleFilter::ALL`] followed by the [`ResampleFilter::ALIASES`].
+    pub const NAMES: [&'static str; Self::ALL.len() + Self::ALIASES.len()] = {
+        let mut names = [""; Self::ALL.len() + Self::ALIASES.len()];
+        let mut i = 0;
+        while i < ResampleFilter::ALL.len() {
+            names[i] = ResampleFilter::ALL[i].name();
+            i += 1;
+        }
+        while i < names.len() {
+            names[i] = ResampleFilter::ALIASES[i - ResampleFilter::ALL.len()].0;
+            i += 1;
+        }
+        names
+    };
+
+    pub const fn name(&self) -> &'static str {
+        match self {
+            ResampleFilter::Nearest => "nearest",
+            ResampleFilter::Box => "box",
+            ResampleFilter::Bilinear => "bilinear",
+            ResampleFilter::Mitchell => "mitchell",
+            ResampleFilter::CatmullRom => "catmull-rom",
+            ResampleFilter::Lanczos3 => "lanczos3",
+        }
+    }
+
+    pub fn by_name(name: &str) -> Option<ResampleFilter> {
+        ResampleFilter::ALL
+            .into_iter()
+            .map(|filter| (filter.name(), filter))
+            .chain(ResampleFilter::ALIASES)
+            .find(|&(filter_name, _)| filter_name == name)
+            .map(|(_, filter)| filter)
+    }
+
+    /// Radius outside of which the filter is zero, in source pixels.
+    fn support(&self) -> f32 {
+        match self {
+            ResampleFilter::Nearest | ResampleFilter::Box => 0.5,
+            ResampleFilter::Bilinear => 1.0,
+            ResampleFilter::Mitchell | ResampleFilter::CatmullRom => 2.0,
+            ResampleFilter::Lanczos3 => 3.0,
+        }
+    }
+
+    fn weight(&self, x: f32) -> f32 {
+        let x: f32 = x.abs();
+        match self {
+            ResampleFilter::Nearest | ResampleFilter::Box => {
+                if x <= 0.5 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            ResampleFilter::Bilinear => (1.0 - x).max(0.0),
+            ResampleFilter::Mitchell => cubic(x, 1.0 / 3.0, 1.0 / 3.0),
+            ResampleFilter::CatmullRom => cubic(x, 0.0, 0.5),
+            ResampleFilter::Lanczos3 => {
+                if x < 3.0 {
+                    sinc(x) * sinc(x / 3.0)
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+/// The Mitchell-Netravali family of cubics.
+fn cubic(x: f32, b: f32, c: f32) -> f32 {
+    let (x2, x3) = (x * x, x * x * x);
+    if x < 1.0 {
+        ((12.0 - 9.0 * b - 6.0 * c) * x3 + (-18.0 + 12.0 * b + 6.0 * c) * x2 + (6.0 - 2.0 * b))
+            / 6.0
+    } else if x < 2.0 {
+        ((-b - 6.0 * c) * x3
+            + (6.0 * b + 30.0 * c) * x2
+            + (-12.0 * b - 48.0 * c) * x
+            + (8.0 * b + 24.0 * c))
+            / 6.0
+    } else {
+        0.0
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    if x == 0.0 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+/// How [`resize_to`] treats a target box of another aspect ratio.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum FitMode {
+    /// Exactly the target size, distorting the image.
+    #[default]
+    Stretch,
+    /// The largest size that fits inside the target, keeping the aspect
+    /// ratio; one side may come out shorter.
+    Fit,
+    /// Covers the target keeping the aspect ratio, with the overflow
+    /// cropped evenly from both sides.
+    Fill,
+}
+
+impl FitMode {
+    pub fn by_name(name: &str) -> Option<FitMode> {
+        match name {
+            "stretch" => Some(FitMode::Stretch),
+            "fit" => Some(FitMode::Fit),
+            "fill" => Some(FitMode::Fill),
+            _ => None,
+        }
+    }
+}
+
+/// Source pixels and normalized weights of every output pixel of one axis.
+fn contributions(src: u32, dst: u32, filter: ResampleFilter) -> Vec<Vec<(usize, f32)>> {
+    let scale: f32 = src as f32 / dst as f32;
+    // Widen the filter when shrinking, so it averages over all the source
+    // pixels an output pixel covers.
+    let stretch: f32 = scale.max(1.0);
+    let support: f32 = filter.support() * stretch;
+    (0..dst)
+        .map(|i| {
+            let center: f32 = (i as f32 + 0.5) * scale;
+            if filter == ResampleFilter::Nearest {
+                return vec![((center as usize).min(src as usize - 1), 1.0)];
+            }
+            let first: i64 = (center - support).floor() as i64;
+            let last: i64 = (center + support).ceil() as i64;
+            let mut taps: Vec<(usize, f32)> = (first..=last)
+                .map(|j| {
+                    let w: f32 = filter.weight((j as f32 + 0.5 - center) / stretch);
+                    (j.clamp(0, src as i64 - 1) as usize, w)
+                })
+                .filter(|&(_, w)| w != 0.0)
+                .collect();
+            let sum: f32 = taps.iter().map(|&(_, w)| w).sum();
+            if sum == 0.0 {
+                return vec![((center as usize).min(src as usize - 1), 1.0)];
+            }
+            for tap in &mut taps {
+                tap.1 /= sum;
+            }
+            taps
+        })
+        .collect()
+}
+
+/// Resamples `img` to `width` by `height` pixels with `filter`.
+///
+/// Works on any pixel type; images with alpha are filtered premultiplied,
+/// so transparent pixels do not bleed their color. Integer channels are
+/// rounded and clamped, float channels keep the overshoot of the sharper
+/// filters.
+///
+/// With `linear` set, the color channels are averaged in linear light
+/// rather than on the sRGB values, so fine detail such as a checkerboard of
+/// black and white keeps its brightness when scaled down.
+pub fn resize<P, T>(
+    img: &ImageBuffer<P, Vec<T>>,
+    width: u32,
+    height: u32,
+    filter: ResampleFilter,
+    linear: bool,
+) -> ImageBuffer<P, Vec<T>>
+where
+    P: Pixel<Subpixel = T> + Send + Sync,
+    T: Channel,
+{
+    let (src_w, src_h) = img.dimensions();
+    if src_w == 0 || src_h == 0 || width == 0 || height == 0 {
+        return ImageBuffer::new(width, height);
+    }
+    let channels: usize = P::CHANNEL_COUNT as usize;
+    let alpha: Option<usize> = matches!(P::COLOR_MODEL, "RGBA" | "YA").then(|| channels - 1);
+    // Channels in `0.0..=1.0`, premultiplied by alpha.
+    let src: Vec<f32> = img
+        .chunks_exact(channels)
+        .flat_map(|pixel| {
+            let a: f32 = alpha.map_or(1.0, |a| pixel[a].value() / <T as Channel>::MAX);
+            (0..channels).map(move |c| {
+                let v: f32 = pixel[c].value() / <T as Channel>::MAX;
+                if Some(c) == alpha {
+                    v
+                } else if linear {
+                    srgb_to_linear(v) * a
+                } else {
+                    v * a
+                }
+            })
+        })
+        .collect();
+
+    let xs = contributions(src_w, width, filter);
+    let ys = contributions(src_h, height, filter);
+    // Horizontal pass into `width` by `src_h`, then vertical per output.
+    let mut rows: Vec<f32> = vec![0.0; width as usize * src_h as usize * channels];
+    for (y, row) in rows.chunks_exact_mut(width as usize * channels).enumerate() {
+        let src_row: &[f32] = &src[y * src_w as usize * channels..][..src_w as usize * channels];
+        for (x, taps) in xs.iter().enumerate() {
+            for &(j, w) in taps {
+                for c in 0..channels {
+                    row[x * channels + c] += src_row[j * channels + c] * w;
+                }
+            }
+        }
+    }
+    par::from_fn(width, height, |x, y| {
+        let mut acc: [f32; 4] = [0.0; 4];
+        for &(i, w) in &ys[y as usize] {
+            let at: usize = (i * width as usize + x as usize) * channels;
+            for c in 0..channels {
+                acc[c] += rows[at + c] * w;
+            }
+        }
+        let a: f32 = alpha.map_or(1.0, |a| acc[a].clamp(0.0, 1.0));
+        let mut out: [T; 4] = [T::from_value(0.0); 4];
+        for c in 0..channels {
+            let v: f32 = if Some(c) == alpha {
+                a
+            } else if a > 0.0 && linear {
+                linear_to_srgb(acc[c] / a)
+            } else if a > 0.0 {
+                acc[c] / a
+            } else {
+                0.0
+            };
+            out[c] = T::from_value_rounded(v * <T as Channel>::MAX);
+        }
+        *P::from_slice(&out[..channels])
+    })
+}
+
+/// Resizes `img` into a `width` by `height` box according to `mode`, see
+/// [`FitMode`], in linear light if `linear` is set like [`resize`].
+pub fn resize_to<P, T>(
+    img: &ImageBuffer<P, Vec<T>>,
+    width: u32,
+    height: u32,
+    filter: ResampleFilter,
+    mode: FitMode,
+    linear: bool,
+) -> ImageBuffer<P, Vec<T>>
+where
+    P: Pixel<Subpixel = T> + Send + Sync,
+    T: Channel,
+{
+    let (src_w, src_h) = img.dimensions();
+    if src_w == 0 || src_h == 0 {
+        return ImageBuffer::new(width, height);
+    }
+    let (sx, sy) = (width as f64 / src_w as f64, height as f64 / src_h as f64);
+    let scaled = |s: f64| {
+        (
+            ((src_w as f64 * s).round() as u32).max(1),
+            ((src_h as f64 * s).round() as u32).max(1),
+        )
+    };
+    match mode {
+        FitMode::Stretch => resize(img, width, height, filter, linear),
+        FitMode::Fit => {
+            let (w, h) = scaled(sx.min(sy));
+            resize(img, w.min(width), h.min(height), filter, linear)
+        }
+        FitMode::Fill => {
+            let (w, h) = scaled(sx.max(sy));
+            let (w, h) = (w.max(width), h.max(height));
+            let full = resize(img, w, h, filter, linear);
+            let (left, top) = ((w - width) / 2, (h - height) / 2);
+            ImageBuffer::from_fn(width, height, |x, y| *full.get_pixel(x + left, y + top))
+        }
+    }
+}
@@ -0,0 +1,559 @@
+use crate::error::Error;
+use crate::rng::SplitMix64;
+use image::{ImageBuffer, Luma};
+use std::fs;
+use std::path::Path;
+
+const RAW_MAGIC: &[u8; 4] = b"TMAP";
+
+/// Threshold matrix for ordered dithering, tiled over the image.
+///
+/// Holds integer ranks; [`ThresholdMap::value`] normalises them by the
+/// number of levels (the largest rank + 1) into `0.0..1.0`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ThresholdMap {
+    width: u32,
+    height: u32,
+    values: Vec<u32>,
+    levels: u32,
+}
+
+fn malformed(message: &str) -> Error {
+    Error::InvalidParameter(format!("malformed threshold map: {}", message))
+}
+
+/// Splits a generated map name like `bayer8` into its kind and size.
+fn split_name(name: &str) -> Option<(&str, u32)> {
+    let split: usize = name.find(|c: char| c.is_ascii_digit())?;
+    Some((&name[..split], name[split..].parse().ok()?))
+}
+
+/// Checks the side of a generated map of `kind`, which has to be small
+/// enough to build without running out of memory or time.
+fn check_size(kind: &str, size: u32) -> Result<(), Error> {
+    let (label, ok, sizes) = match kind {
+        "bayer" => (
+            "Bayer",
+            size.is_power_of_two() && (2..=16).contains(&size),
+            "2, 4, 8 or 16".to_string(),
+        ),
+        "clustered" | "line" => (
+            if kind == "line" {
+                "line"
+            } else {
+                "clustered-dot"
+            },
+            (1..=ThresholdMap::MAX_SPOT_SIZE).contains(&size),
+            format!("1 to {}", ThresholdMap::MAX_SPOT_SIZE),
+        ),
+        "blue" => (
+            "blue noise",
+            (4..=ThresholdMap::MAX_BLUE_NOISE_SIZE).contains(&size),
+            format!("4 to {}", ThresholdMap::MAX_BLUE_NOISE_SIZE),
+        ),
+        _ => return Ok(()),
+    };
+    if ok {
+        Ok(())
+    } else {
+        Err(Error::InvalidParameter(format!(
+            "{} maps are {} wide, not {}",
+            label, sizes, size
+        )))
+    }
+}
+
+/// Checks the size in a generated map name, see [`ThresholdMap::by_name`],
+/// without building the map. Other names are left to the file loaders.
+pub(crate) fn check_name(name: &str) -> Result<(), Error> {
+    match split_name(name) {
+        Some((kind, size)) => check_size(kind, size),
+        None => Ok(()),
+    }
+}
+
+impl<const N: usize> From<[[u8; N]; N]> for ThresholdMap {
+    fn from(mat: [[u8; N]; N]) -> Self {
+        let values: Vec<u32> = mat.iter().flatten().map(|&v| v as u32).collect();
+        ThresholdMap::new(N as u32, N as u32, values).expect("N > 0")
+    }
+}
+
+impl ThresholdMap {
+    /// Largest side of a generated clustered-dot or line map.
+    pub const MAX_SPOT_SIZE: u32 = 256;
+
+    /// Largest side of a generated blue-noise map.
+    pub const MAX_BLUE_NOISE_SIZE: u32 = 128;
+
+    /// Builds a `width` x `height` map from row-major `values`.
+    ///
+    /// Fails with [`Error::InvalidParameter`] if the map would be empty, has
+    /// more cells than fit a `u32`, `values` does not match the dimensions
+    /// or a rank is `u32::MAX`, which leaves no room for the level count.
+    pub fn new(width: u32, height: u32, values: Vec<u32>) -> Result<ThresholdMap, Error> {
+        if width == 0 || height == 0 {
+            return Err(malformed("empty map"));
+        }
+        let cells: u32 = width
+            .checked_mul(height)
+            .ok_or_else(|| malformed(&format!("{}x{} is too large", width, height)))?;
+        if values.len() != cells as usize {
+            return Err(malformed(&format!(
+                "{} ranks do not fill {}x{}",
+                values.len(),
+                width,
+                height
+            )));
+        }
+        let max: u32 = values.iter().copied().max().unwrap_or(0);
+        let levels: u32 = max
+            .checked_add(1)
+            .ok_or_else(|| malformed(&format!("rank {} is too large", max)))?;
+        Ok(ThresholdMap {
+            width,
+            height,
+            values,
+            levels,
+        })
+    }
+
+    /// Recursive Bayer matrix of side `size`, a power of two from 2 to 16.
+    ///
+    /// `ThresholdMap::bayer(8)` equals [`crate::BAYER_8X8`].
+    pub fn bayer(size: u32) -> Result<ThresholdMap, Error> {
+        check_size("bayer", size)?;
+        const BASE: [[u32; 2]; 2] = [[0, 2], [3, 1]];
+        let mut values: Vec<u32> = vec![0];
+        let mut n: u32 = 1;
+        while n < size {
+            let mut next: Vec<u32> = vec![0; (4 * n * n) as usize];
+            for i in 0..2 * n {
+                for j in 0..2 * n {
+                    next[(i * 2 * n + j) as usize] = 4 * values[((i % n) * n + j % n) as usize]
+                        + BASE[(i / n) as usize][(j / n) as usize];
+                }
+            }
+            values = next;
+            n *= 2;
+        }
+        ThresholdMap::new(size, size, values)
+    }
+
+    /// Clustered-dot matrix of side `size`, 1 to
+    /// [`ThresholdMap::MAX_SPOT_SIZE`]: thresholds grow outwards from the
+    /// cell centre, so dots grow as round clusters like print halftones.
+    pub fn clustered_dot(size: u32) -> Result<ThresholdMap, Error> {
+        check_size("clustered", size)?;
+        ThresholdMap::from_spot(size, size, |u, v| u * u + v * v)
+    }
+
+    /// Line screen of period `size`, 1 to [`ThresholdMap::MAX_SPOT_SIZE`]:
+    /// thresholds grow outwards from the middle row, giving horizontal
+    /// halftone lines.
+    pub fn halftone_line(size: u32) -> Result<ThresholdMap, Error> {
+        check_size("line", size)?;
+        ThresholdMap::from_spot(size, size, |_, v| v.abs())
+    }
+
+    /// Ranks the cells of a `width` x `height` map by the spot function
+    /// `spot(u, v)`, with `u` and `v` the cell centre in `-1.0..1.0`.
+    fn from_spot(
+        width: u32,
+        height: u32,
+        spot: impl Fn(f32, f32) -> f32,
+    ) -> Result<ThresholdMap, Error> {
+        let cells: u32 = width
+            .checked_mul(height)
+            .filter(|&cells| cells > 0)
+            .ok_or_else(|| malformed(&format!("cannot build {}x{}", width, height)))?;
+        let mut cells: Vec<(f32, u32)> = (0..cells)
+            .map(|n| {
+                let u: f32 = 2.0 * ((n % width) as f32 + 0.5) / width as f32 - 1.0;
+                let v: f32 = 2.0 * ((n / width) as f32 + 0.5) / height as f32 - 1.0;
+                (spot(u, v), n)
+            })
+            .collect();
+        cells.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+        let mut values: Vec<u32> = vec![0; cells.len()];
+        for (rank, &(_, n)) in cells.iter().enumerate() {
+            values[n as usize] = rank as u32;
+        }
+        ThresholdMap::new(width, height, values)
+    }
+
+    /// Blue-noise map of side `size` (4 to
+    /// [`ThresholdMap::MAX_BLUE_NOISE_SIZE`]) generated with Ulichney's
+    /// void-and-cluster algorithm. The same `seed` always yields the same map.
+    ///
+    /// Generation is quadratic in the number of cells, so large maps are
+    /// best generated once and cached with [`ThresholdMap::save_png`] or
+    /// [`ThresholdMap::save_raw`].
+    pub fn blue_noise(size: u32, seed: u64) -> Result<ThresholdMap, Error> {
+        check_size("blue", size)?;
+        let len: usize = (size * size) as usize;
+        let mut field = VoidCluster::new(size as usize);
+        let mut rng = SplitMix64::new(seed);
+        let ones: usize = len / 10;
+        let mut placed: usize = 0;
+        while placed < ones {
+            let p: usize = rng.below(len);
+            if !field.pattern[p] {
+                field.set(p, true);
+                placed += 1;
+            }
+        }
+        for _ in 0..len {
+            let cluster: usize = field.tightest_cluster();
+            field.set(cluster, false);
+            let void: usize = field.largest_void();
+            field.set(void, true);
+            if void == cluster {
+                break;
+            }
+        }
+
+        let mut values: Vec<u32> = vec![0; len];
+        let mut removing = field.clone();
+        for rank in (0..ones).rev() {
+            let cluster: usize = removing.tightest_cluster();
+            removing.set(cluster, false);
+            values[cluster] = rank as u32;
+        }
+        for rank in ones..len {
+            let void: usize = field.largest_void();
+            field.set(void, true);
+            values[void] = rank as u32;
+        }
+        ThresholdMap::new(size, size, values)
+    }
+
+    /// Parses a matrix written as one row per line of whitespace or comma
+    /// separated integers. Empty lines and `#` comments are skipped.
+    pub fn from_text(text: &str) -> Result<ThresholdMap, Error> {
+        let mut rows: Vec<Vec<u32>> = vec![];
+        for (n, line) in text.lines().enumerate() {
+            let line: &str = line.split('#').next().unwrap_or("");
+            let row: Vec<u32> = line
+                .split(|c: char| c.is_whitespace() || c == ',')
+                .filter(|val| !val.is_empty())
+                .map(|val| val.parse().ok())
+                .collect::<Option<_>>()
+                .ok_or_else(|| malformed(&format!("line {} is not a row of ranks", n + 1)))?;
+            if !row.is_empty() {
+                rows.push(row);
+            }
+        }
+        let width: usize = rows.first().ok_or_else(|| malformed("no rows"))?.len();
+        if rows.iter().any(|row| row.len() != width) {
+            return Err(malformed("rows differ in length"));
+        }
+        ThresholdMap::new(width as u32, rows.len() as u32, rows.concat())
+    }
+
+    /// Loads a matrix in the [`ThresholdMap::from_text`] format.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<ThresholdMap, Error> {
+        ThresholdMap::from_text(&fs::read_to_string(path)?)
+    }
+
+    /// Writes the ranks as a 16-bit greyscale PNG.
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let ranks: Vec<u16> = self
+            .values
+            .iter()
+            .map(|&v| u16::try_from(v))
+            .collect::<Result<_, _>>()
+            .map_err(|_| malformed("too many levels for PNG"))?;
+        ImageBuffer::<Luma<u16>, Vec<u16>>::from_raw(self.width, self.height, ranks)
+            .expect("dimensions match")
+            .save(path)
+            .map_err(Error::encode)
+    }
+
+    /// Loads a greyscale image as a map. Grey values are ranked densely, so
+    /// both [`ThresholdMap::save_png`] output and 8-bit noise textures work.
+    pub fn load_png<P: AsRef<Path>>(path: P) -> Result<ThresholdMap, Error> {
+        let img = image::open(path).map_err(Error::decode)?.into_luma16();
+        let (width, height) = img.dimensions();
+        let mut levels: Vec<u16> = img.as_raw().clone();
+        levels.sort_unstable();
+        levels.dedup();
+        let values: Vec<u32> = img
+            .as_raw()
+            .iter()
+            .map(|v| levels.binary_search(v).expect("value is a level") as u32)
+            .collect();
+        ThresholdMap::new(width, height, values)
+    }
+
+    /// Writes the map as `TMAP`, width and height, then the row-major
+    /// ranks, all as little-endian `u32`.
+    pub fn save_raw<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let mut bytes: Vec<u8> = RAW_MAGIC.to_vec();
+        for v in [self.width, self.height].iter().chain(&self.values) {
+            bytes.extend_from_slice(&v.to_le_bytes());
+        }
+        Ok(fs::write(path, bytes)?)
+    }
+
+    /// Loads a map written by [`ThresholdMap::save_raw`].
+    pub fn load_raw<P: AsRef<Path>>(path: P) -> Result<ThresholdMap, Error> {
+        let bytes: Vec<u8> = fs::read(path)?;
+        let body: &[u8] = bytes
+            .strip_prefix(RAW_MAGIC)
+            .ok_or_else(|| malformed("no TMAP header"))?;
+        if !body.len().is_multiple_of(4) {
+            return Err(malformed("truncated ranks"));
+        }
+        let words: Vec<u32> = body
+            .chunks_exact(4)
+            .map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]]))
+            .collect();
+        match words.as_slice() {
+            [width, height, values @ ..] => ThresholdMap::new(*width, *height, values.to_vec()),
+            _ => Err(malformed("truncated header")),
+        }
+    }
+
+    /// Loads a map, picking the format by extension: `png` for
+    /// [`ThresholdMap::load_png`], `tmap` for [`ThresholdMap::load_raw`] and
+    /// the text format otherwise.
+    pub fn load_any<P: AsRef<Path>>(path: P) -> Result<ThresholdMap, Error> {
+        let ext: Option<String> = path
+            .as_ref()
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase());
+        match ext.as_deref() {
+            Some("png") => ThresholdMap::load_png(path),
+            Some("tmap") => ThresholdMap::load_raw(path),
+            _ => ThresholdMap::load(path),
+        }
+    }
+
+    /// Resolves `bayer<N>`, `clustered<N>`, `line<N>` and `blue<N>` names,
+    /// e.g. `bayer8` or `clustered6`. Blue noise is generated with seed 0.
+    pub fn by_name(name: &str) -> Option<ThresholdMap> {
+        let (kind, size) = split_name(name)?;
+        match kind {
+            "bayer" => ThresholdMap::bayer(size).ok(),
+            "clustered" => ThresholdMap::clustered_dot(size).ok(),
+            "line" => ThresholdMap::halftone_line(size).ok(),
+            "blue" => ThresholdMap::blue_noise(size, 0).ok(),
+            _ => None,
+        }
+    }
+
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Number of distinct threshold steps the map is normalised by.
+    pub fn levels(&self) -> u32 {
+        self.levels
+    }
+
+    /// Raw rank at `(x, y)`, wrapping around the map edges.
+    pub fn rank(&self, x: u32, y: u32) -> u32 {
+        self.values[((y % self.height) * self.width + x % self.width) as usize]
+    }
+
+    /// Normalised threshold in `0.0..1.0` at `(x, y)`, wrapping around the
+    /// map edges.
+    pub fn value(&self, x: u32, y: u32) -> f32 {
+        self.rank(x, y) as f32 / self.levels as f32
+    }
+}
+
+/// Binary pattern plus its Gaussian-filtered density on a torus, the state
+/// of the void-and-cluster search.
+#[derive(Clone)]
+struct VoidCluster {
+    size: usize,
+    gauss: Vec<f32>,
+    pattern: Vec<bool>,
+    energy: Vec<f32>,
+}
+
+impl VoidCluster {
+    const SIGMA: f32 = 1.5;
+
+    fn new(size: usize) -> VoidCluster {
+        let mut gauss: Vec<f32> = vec![0.0; size * size];
+        for dy in 0..size {
+            for dx in 0..size {
+                let (wx, wy) = (dx.min(size - dx), dy.min(size - dy));
+                gauss[dy * size + dx] =
+                    (-((wx * wx + wy * wy) as f32) / (2.0 * Self::SIGMA * Self::SIGMA)).exp();
+            }
+        }
+        VoidCluster {
+            size,
+            gauss,
+            pattern: vec![false; size * size],
+            energy: vec![0.0; size * size],
+        }
+    }
+
+    fn set(&mut self, p: usize, on: bool) {
+        self.pattern[p] = on;
+        let sign: f32 = if on { 1.0 } else { -1.0 };
+        let n: usize = self.size;
+        let (px, py) = (p % n, p / n);
+        for qy in 0..n {
+            let dy: usize = (qy + n - py) % n;
+            for qx in 0..n {
+                let dx: usize = (qx + n - px) % n;
+                self.energy[qy * n + qx] += sign * self.gauss[dy * n + dx];
+            }
+        }
+    }
+
+    /// Set cell with the highest density around it.
+    fn tightest_cluster(&self) -> usize {
+        self.extreme(true, |e, best| e > best)
+    }
+
+    /// Unset cell with the lowest density around it.
+    fn largest_void(&self) -> usize {
+        self.extreme(false, |e, best| e < best)
+    }
+
+    fn extreme(&self, on: bool, better: impl Fn(f32, f32) -> bool) -> usize {
+        let mut found: Option<usize> = None;
+        for p in 0..self.pattern.len() {
+            if self.pattern[p] == on
+                && found.is_none_or(|best| better(self.energy[p], self.energy[best]))
+            {
+                found = Some(p);
+            }
+        }
+        found.expect("pattern is neither empty nor full")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rejected<T>(map: Result<T, Error>) -> bool {
+        matches!(map, Err(Error::InvalidParameter(_)))
+    }
+
+    #[test]
+    fn new_rejects_bad_sizes_and_ranks() {
+        assert!(ThresholdMap::new(2, 2, vec![0, 3, 2, 1]).is_ok());
+        assert!(rejected(ThresholdMap::new(0, 2, vec![])));
+        assert!(rejected(ThresholdMap::new(2, 2, vec![0, 1, 2])));
+        assert!(rejected(ThresholdMap::new(65536, 65536, vec![0])));
+        assert!(rejected(ThresholdMap::new(1, 1, vec![u32::MAX])));
+        assert!(rejected(ThresholdMap::bayer(3)));
+        assert!(rejected(ThresholdMap::blue_noise(2, 0)));
+        assert!(rejected(ThresholdMap::clustered_dot(0)));
+    }
+
+    /// Whether the ranks of `map` are `0..n` for its `n` cells, each once.
+    fn is_permutation(map: &ThresholdMap) -> bool {
+        let mut ranks: Vec<u32> = map.values.clone();
+        ranks.sort_unstable();
+        ranks.into_iter().eq(0..map.values.len() as u32)
+    }
+
+    #[test]
+    fn bayer8_is_the_classic_matrix() {
+        assert_eq!(
+            ThresholdMap::bayer(8).unwrap(),
+            ThresholdMap::from(crate::BAYER_8X8)
+        );
+        assert_eq!(ThresholdMap::bayer(2).unwrap().values, [0, 2, 3, 1]);
+        for size in [2, 4, 8, 16] {
+            let map = ThresholdMap::bayer(size).unwrap();
+            assert!(is_permutation(&map), "bayer{}", size);
+            assert_eq!(map.levels(), size * size);
+        }
+    }
+
+    #[test]
+    fn spot_maps_rank_every_cell_once() {
+        for size in 1..=12 {
+            for map in [
+                ThresholdMap::clustered_dot(size).unwrap(),
+                ThresholdMap::halftone_line(size).unwrap(),
+            ] {
+                assert_eq!(map.dimensions(), (size, size));
+                assert!(is_permutation(&map), "{}: {:?}", size, map.values);
+            }
+        }
+        // The centre of a clustered dot turns on first, a line's middle row
+        // before the outer ones.
+        assert_eq!(ThresholdMap::clustered_dot(3).unwrap().rank(1, 1), 0);
+        let line = ThresholdMap::halftone_line(3).unwrap();
+        assert!((0..3).all(|x| line.rank(x, 1) < 3 && line.rank(x, 0) >= 3));
+    }
+
+    #[test]
+    fn blue_noise_is_a_seeded_permutation() {
+        for size in [4, 8, 16] {
+            let map = ThresholdMap::blue_noise(size, 7).unwrap();
+            assert!(is_permutation(&map), "blue{}", size);
+            assert_eq!(map, ThresholdMap::blue_noise(size, 7).unwrap());
+        }
+        assert_ne!(
+            ThresholdMap::blue_noise(16, 7).unwrap(),
+            ThresholdMap::blue_noise(16, 8).unwrap()
+        );
+        assert_eq!(
+            ThresholdMap::by_name("blue8"),
+            ThresholdMap::blue_noise(8, 0).ok()
+        );
+    }
+
+    #[test]
+    fn generated_maps_are_capped() {
+        let max = ThresholdMap::MAX_SPOT_SIZE;
+        assert!(rejected(ThresholdMap::clustered_dot(max + 1)));
+        assert!(rejected(ThresholdMap::halftone_line(u32::MAX)));
+        assert!(rejected(ThresholdMap::blue_noise(
+            ThresholdMap::MAX_BLUE_NOISE_SIZE + 1,
+            0
+        )));
+        assert!(rejected(check_name("clustered65535")));
+        assert!(rejected(check_name("blue129")));
+        assert!(rejected(check_name("bayer32")));
+        assert!(check_name("line256").is_ok());
+        assert!(check_name("maps/noise.png").is_ok());
+    }
+
+    #[test]
+    fn png_and_raw_files_round_trip() {
+        let dir = std::env::temp_dir();
+        let maps = [
+            ThresholdMap::bayer(16).unwrap(),
+            ThresholdMap::clustered_dot(5).unwrap(),
+            ThresholdMap::new(3, 2, vec![5, 0, 2, 1, 4, 3]).unwrap(),
+        ];
+        for (n, map) in maps.iter().enumerate() {
+            let stem = dir.join(format!("round-trip-{}-{}", std::process::id(), n));
+            let (png, raw) = (stem.with_extension("png"), stem.with_extension("tmap"));
+            map.save_png(&png).unwrap();
+            map.save_raw(&raw).unwrap();
+            assert_eq!(&ThresholdMap::load_any(&png).unwrap(), map);
+            assert_eq!(&ThresholdMap::load_any(&raw).unwrap(), map);
+            let _ = fs::remove_file(png);
+            let _ = fs::remove_file(raw);
+        }
+    }
+
+    #[test]
+    fn load_raw_rejects_crafted_headers() {
+        let path = std::env::temp_dir().join(format!("crafted-{}.tmap", std::process::id()));
+        for words in [[65536, 65536, 0], [1, 1, u32::MAX]] {
+            let mut bytes: Vec<u8> = RAW_MAGIC.to_vec();
+            for word in words {
+                bytes.extend_from_slice(&u32::to_le_bytes(word));
+            }
+            fs::write(&path, bytes).unwrap();
+            assert!(rejected(ThresholdMap::load_raw(&path)), "{:?}", words);
+        }
+        let _ = fs::remove_file(path);
+    }
+}
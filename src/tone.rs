@@ -0,0 +1,186 @@
+use crate::depth::Channel;
+use crate::error::Error;
+use crate::par;
+use image::{ImageBuffer, Pixel, Rgb};
+
+/// The value contrast is stretched around.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Pivot {
+    /// Half of full intensity.
+    #[default]
+    MidGrey,
+    /// The mean of all channels of the image, so its average brightness
+    /// stays put.
+    Mean,
+}
+
+impl Pivot {
+    pub fn by_name(name: &str) -> Option<Pivot> {
+        match name {
+            "mid-grey" | "mid-gray" => Some(Pivot::MidGrey),
+            "mean" => Some(Pivot::Mean),
+            _ => None,
+        }
+    }
+}
+
+/// Scales the distance of every channel from `pivot` by `contrast`, then
+/// multiplies it by `brightness` like [`crate::bright`].
+///
+/// `1.0` leaves the image unchanged for both; a `contrast` of `0.0` turns
+/// it into flat pivot grey.
+pub fn brightness_contrast<T: Channel>(
+    img: &mut ImageBuffer<Rgb<T>, Vec<T>>,
+    brightness: f32,
+    contrast: f32,
+    pivot: Pivot,
+) where
+    Rgb<T>: Pixel<Subpixel = T>,
+{
+    let p: f32 = match pivot {
+        Pivot::MidGrey => T::MAX / 2.0,
+        Pivot::Mean if img.is_empty() => T::MAX / 2.0,
+        Pivot::Mean => {
+            let sum: f64 = img.iter().map(|v| v.value() as f64).sum();
+            (sum / img.len() as f64) as f32
+        }
+    };
+    par::for_each_pixel(img, |pixel| {
+        pixel.0 = pixel
+            .0
+            .map(|v| T::from_value(((v.value() - p) * contrast + p) * brightness))
+    });
+}
+
+/// Maps `black..=white` to the full range and applies `gamma` to the
+/// midtones; values outside are clipped, for float images too.
+///
+/// The points are fractions of full intensity, so `black = 0.1` is 25 in an
+/// 8-bit image. A `gamma` above 1 brightens the midtones. `white` has to be
+/// greater than `black`.
+pub fn levels<T: Channel>(img: &mut ImageBuffer<Rgb<T>, Vec<T>>, black: f32, white: f32, gamma: f32)
+where
+    Rgb<T>: Pixel<Subpixel = T>,
+{
+    par::for_each_pixel(img, |pixel| {
+        pixel.0 = pixel.0.map(|v| {
+            let v: f32 = ((v.value() / T::MAX - black) / (white - black)).clamp(0.0, 1.0);
+            T::from_value_rounded(v.powf(1.0 / gamma) * T::MAX)
+        })
+    });
+}
+
+/// A tone curve through control points, interpolated with monotone cubic
+/// splines so it never overshoots between them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Curve {
+    points: Vec<(f32, f32)>,
+    slopes: Vec<f32>,
+}
+
+impl Curve {
+    /// Curve through `points`, `(input, output)` pairs as fractions of full
+    /// intensity. Needs at least two points with distinct inputs; they are
+    /// sorted by input. Inputs outside the first and last point keep the
+    /// output of that point. Other points fail with
+    /// [`Error::InvalidParameter`].
+    pub fn new(points: &[(f32, f32)]) -> Result<Curve, Error> {
+        let mut points: Vec<(f32, f32)> = points.to_vec();
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        if points.iter().any(|p| p.0.is_nan() || p.1.is_nan()) {
+            return Err(Error::InvalidParameter(
+                "curve points must be numbers".to_string(),
+            ));
+        }
+        if points.len() < 2 {
+            return Err(Error::InvalidParameter(
+                "a curve needs at least two points".to_string(),
+            ));
+        }
+        if let Some(w) = points.windows(2).find(|w| w[0].0 == w[1].0) {
+            return Err(Error::InvalidParameter(format!(
+                "two curve points have the input {}",
+                w[0].0
+            )));
+        }
+
+        // Fritsch-Carlson: secant slopes, averaged at the points and limited
+        // where the curve would stop being monotone.
+        let secants: Vec<f32> = points
+            .windows(2)
+            .map(|w| (w[1].1 - w[0].1) / (w[1].0 - w[0].0))
+            .collect();
+        let n: usize = points.len();
+        let mut slopes: Vec<f32> = vec![0.0; n];
+        slopes[0] = secants[0];
+        slopes[n - 1] = secants[n - 2];
+        for i in 1..n - 1 {
+            slopes[i] = if secants[i - 1] * secants[i] <= 0.0 {
+                0.0
+            } else {
+                (secants[i - 1] + secants[i]) / 2.0
+            };
+        }
+        for i in 0..n - 1 {
+            if secants[i] == 0.0 {
+                slopes[i] = 0.0;
+                slopes[i + 1] = 0.0;
+                continue;
+            }
+            let a: f32 = slopes[i] / secants[i];
+            let b: f32 = slopes[i + 1] / secants[i];
+            let h: f32 = a.hypot(b);
+            if h > 3.0 {
+                slopes[i] = 3.0 * a / h * secants[i];
+                slopes[i + 1] = 3.0 * b / h * secants[i];
+            }
+        }
+        Ok(Curve { points, slopes })
+    }
+
+    /// Parses `x:y` pairs separated by commas, e.g. `0:0,0.25:0.2,1:1`.
+    pub fn parse(text: &str) -> Result<Curve, Error> {
+        let invalid = || Error::InvalidParameter(format!("invalid curve: `{}`", text));
+        let points: Option<Vec<(f32, f32)>> = text
+            .split(',')
+            .map(|pair| {
+                let (x, y) = pair.trim().split_once(':')?;
+                Some((x.trim().parse().ok()?, y.trim().parse().ok()?))
+            })
+            .collect();
+        Curve::new(&points.ok_or_else(invalid)?)
+            .map_err(|err| err.context(&format!("invalid curve: `{}`", text)))
+    }
+
+    /// The output for input `x`.
+    pub fn eval(&self, x: f32) -> f32 {
+        let (first, last) = (self.points[0], self.points[self.points.len() - 1]);
+        if x.is_nan() || x <= first.0 {
+            return first.1;
+        }
+        if x >= last.0 {
+            return last.1;
+        }
+        let i: usize = self.points.partition_point(|p| p.0 <= x) - 1;
+        let ((x0, y0), (x1, y1)) = (self.points[i], self.points[i + 1]);
+        let h: f32 = x1 - x0;
+        let t: f32 = (x - x0) / h;
+        let (t2, t3) = (t * t, t * t * t);
+        (2.0 * t3 - 3.0 * t2 + 1.0) * y0
+            + (t3 - 2.0 * t2 + t) * h * self.slopes[i]
+            + (-2.0 * t3 + 3.0 * t2) * y1
+            + (t3 - t2) * h * self.slopes[i + 1]
+    }
+}
+
+/// Maps every channel through `curve`.
+pub fn curves<T: Channel>(img: &mut ImageBuffer<Rgb<T>, Vec<T>>, curve: &Curve)
+where
+    Rgb<T>: Pixel<Subpixel = T>,
+{
+    par::for_each_pixel(img, |pixel| {
+        pixel.0 = pixel
+            .0
+            .map(|v| T::from_value_rounded(curve.eval(v.value() / T::MAX) * T::MAX))
+    });
+}
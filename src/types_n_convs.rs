@@ -1,6 +1,21 @@
+use crate::error::Error;
 use image::Rgb;
 
-pub fn str_to_pal(hex_str: &str) -> Option<Vec<[u8; 3]>> {
+/// `name` lowercased and without `-`, `_` or space separators, for lookups
+/// by name that accept `Floyd-Steinberg` as well as `floyd_steinberg`.
+pub(crate) fn normalize_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| !matches!(c, '-' | '_' | ' '))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Parses concatenated 6-digit hex colors (`"000000ffffff"`) into a palette.
+///
+/// Fails with [`Error::InvalidPalette`] if the string is empty, its length
+/// is not a multiple of 6 or a digit is not hexadecimal. See
+/// [`crate::palette_io`] for palette files.
+pub fn str_to_pal(hex_str: &str) -> Result<Vec<[u8; 3]>, Error> {
     fn c_val(c: u8) -> Option<u8> {
         match c {
             b'0'..=b'9' => Some(c - b'0'),
@@ -10,34 +25,43 @@ pub fn str_to_pal(hex_str: &str) -> Option<Vec<[u8; 3]>> {
         }
     }
 
-    fn hex_val(l: u8, h: u8) -> Option<u8> {
-        let l_val = c_val(l);
-        let h_val = c_val(h);
-        if l_val.is_some() && h_val.is_some() {
-            Some((h_val.unwrap() << 4) | l_val.unwrap())
-        } else {
-            None
-        }
+    fn hex_val(h: u8, l: u8) -> Option<u8> {
+        Some((c_val(h)? << 4) | c_val(l)?)
     }
 
+    if hex_str.is_empty() || !hex_str.len().is_multiple_of(6) {
+        return Err(Error::InvalidPalette(format!(
+            "`{}` is not a list of 6-digit hex colors",
+            hex_str
+        )));
+    }
     hex_str
         .as_bytes()
         .chunks_exact(6)
         .map(|hex| {
-            let r_val = hex_val(hex[0], hex[1]);
-            let g_val = hex_val(hex[2], hex[3]);
-            let b_val = hex_val(hex[4], hex[5]);
-            if r_val.is_some() && g_val.is_some() && b_val.is_some() {
-                Some([r_val.unwrap(), g_val.unwrap(), b_val.unwrap()])
-            } else {
-                None
-            }
+            let color = || {
+                Some([
+                    hex_val(hex[0], hex[1])?,
+                    hex_val(hex[2], hex[3])?,
+                    hex_val(hex[4], hex[5])?,
+                ])
+            };
+            color().ok_or_else(|| {
+                Error::InvalidPalette(format!(
+                    "invalid hex color `{}`",
+                    String::from_utf8_lossy(hex)
+                ))
+            })
         })
         .collect()
 }
 
+/// Numeric types the saturating `u8_*` helpers below accept as operands:
+/// `u8`, `i32` and `f32`.
 pub trait Basic: Sized {
+    /// The value clamped to `0..=255`, fractions truncated.
     fn to_u8(val: Self) -> u8;
+    /// The value as `i32`, fractions truncated.
     fn to_i32(val: Self) -> i32;
     fn to_f32(val: Self) -> f32;
 }
@@ -90,55 +114,48 @@ impl Basic for f32 {
     }
 }
 
-pub fn i32_add<T: Basic, Y: Basic>(one: T, two: Y) -> i32 {
-    T::to_i32(one) + Y::to_i32(two)
-}
-
-pub fn i32_sub<T: Basic, Y: Basic>(one: T, two: Y) -> i32 {
-    return T::to_i32(one) - Y::to_i32(two);
-}
-
-pub fn i32_div<T: Basic, Y: Basic>(one: T, two: Y) -> i32 {
-    return (T::to_f32(one) / Y::to_f32(two)) as i32;
-}
-
-pub fn i32_mul<T: Basic, Y: Basic>(one: T, two: Y) -> i32 {
-    return (T::to_f32(one) * Y::to_f32(two)) as i32;
+/// Converts both operands to `i32` and subtracts them.
+pub(crate) fn i32_sub<T: Basic, Y: Basic>(one: T, two: Y) -> i32 {
+    T::to_i32(one) - Y::to_i32(two)
 }
 
+/// Adds the operands, clamping the result to `0..=255`.
 pub fn u8_add<T: Basic, Y: Basic>(one: T, two: Y) -> u8 {
     let add: i32 = T::to_i32(one) + Y::to_i32(two);
-    return if add > 255 {
+    if add > 255 {
         255
     } else if add < 0 {
         0
     } else {
         add as u8
-    };
+    }
 }
 
+/// Subtracts the operands, clamping the result to `0..=255`.
 pub fn u8_sub<T: Basic, Y: Basic>(one: T, two: Y) -> u8 {
     let sub: i32 = T::to_i32(one) - Y::to_i32(two);
-    return if sub > 255 {
+    if sub > 255 {
         255
     } else if sub < 0 {
         0
     } else {
         sub as u8
-    };
+    }
 }
 
+/// Multiplies the operands, clamping the result to `0..=255`.
 pub fn u8_mul<T: Basic, Y: Basic>(one: T, two: Y) -> u8 {
     let mul: f32 = T::to_f32(one) * Y::to_f32(two);
-    return if mul > 255.0 {
+    if mul > 255.0 {
         255
     } else if mul < 0.0 {
         0
     } else {
         mul as u8
-    };
+    }
 }
 
+/// Divides the operands, clamping the result to `0..=255`.
 pub fn u8_div<T: Basic, Y: Basic>(one: T, two: Y) -> u8 {
     let div: f32 = T::to_f32(one) / Y::to_f32(two);
     if div > 255.0 {
@@ -150,14 +167,17 @@ pub fn u8_div<T: Basic, Y: Basic>(one: T, two: Y) -> u8 {
     }
 }
 
+/// Widens a pixel to `i32` channels for signed arithmetic.
 pub fn rgb_to_arr(one: Rgb<u8>) -> [i32; 3] {
     [one[0] as i32, one[1] as i32, one[2] as i32]
 }
 
+/// Grey pixel with all channels set to `n`.
 pub fn u8_to_rgb(n: u8) -> Rgb<u8> {
     Rgb([n, n, n])
 }
 
+/// Channel-wise saturating addition.
 pub fn rgb_add(one: Rgb<u8>, two: Rgb<u8>) -> Rgb<u8> {
     Rgb([
         u8_add(one[0], two[0]),
@@ -166,6 +186,7 @@ pub fn rgb_add(one: Rgb<u8>, two: Rgb<u8>) -> Rgb<u8> {
     ])
 }
 
+/// Channel-wise saturating subtraction.
 pub fn rgb_sub(one: Rgb<u8>, two: Rgb<u8>) -> Rgb<u8> {
     Rgb([
         u8_sub(one[0], two[0]),
@@ -174,6 +195,7 @@ pub fn rgb_sub(one: Rgb<u8>, two: Rgb<u8>) -> Rgb<u8> {
     ])
 }
 
+/// Divides every channel by `two`, saturating.
 pub fn rgb_div(one: Rgb<u8>, two: f32) -> Rgb<u8> {
     Rgb([
         u8_div(one[0], two),
@@ -182,6 +204,7 @@ pub fn rgb_div(one: Rgb<u8>, two: f32) -> Rgb<u8> {
     ])
 }
 
+/// Multiplies every channel by `two`, saturating.
 pub fn rgb_mul(one: Rgb<u8>, two: f32) -> Rgb<u8> {
     Rgb([
         u8_mul(one[0], two),
@@ -189,3 +212,129 @@ pub fn rgb_mul(one: Rgb<u8>, two: f32) -> Rgb<u8> {
         u8_mul(one[2], two),
     ])
 }
+
+/// Decodes an sRGB channel value in `0.0..=1.0` to linear light.
+pub fn srgb_to_linear(v: f32) -> f32 {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encodes a linear light channel value in `0.0..=1.0` as sRGB.
+pub fn linear_to_srgb(v: f32) -> f32 {
+    if v <= 0.0031308 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Linear light channels in `0.0..=1.0` of an sRGB color.
+pub fn rgb_to_linear(color: [u8; 3]) -> [f32; 3] {
+    [
+        srgb_to_linear(color[0] as f32 / 255.0),
+        srgb_to_linear(color[1] as f32 / 255.0),
+        srgb_to_linear(color[2] as f32 / 255.0),
+    ]
+}
+
+/// CIELAB coordinates of an sRGB color under the D65 white point.
+pub fn rgb_to_lab(color: [u8; 3]) -> [f32; 3] {
+    fn f(t: f32) -> f32 {
+        if t > 216.0 / 24389.0 {
+            t.cbrt()
+        } else {
+            (24389.0 / 27.0 * t + 16.0) / 116.0
+        }
+    }
+
+    let [r, g, b] = rgb_to_linear(color);
+    let x: f32 = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+    let y: f32 = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z: f32 = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+/// Oklab coordinates of an sRGB color.
+pub fn rgb_to_oklab(color: [u8; 3]) -> [f32; 3] {
+    let [r, g, b] = rgb_to_linear(color);
+    let l: f32 = (0.41222147 * r + 0.53633254 * g + 0.051445993 * b).cbrt();
+    let m: f32 = (0.2119035 * r + 0.6806995 * g + 0.10739696 * b).cbrt();
+    let s: f32 = (0.08830246 * r + 0.28171884 * g + 0.6299787 * b).cbrt();
+    [
+        0.21045426 * l + 0.7936178 * m - 0.004072047 * s,
+        1.9779985 * l - 2.4285922 * m + 0.4505937 * s,
+        0.025904037 * l + 0.78277177 * m - 0.80867577 * s,
+    ]
+}
+
+/// Hue in degrees `0.0..360.0`, and the chroma, maximum and minimum of RGB
+/// channels in `0.0..=1.0`.
+fn hue_chroma(rgb: [f32; 3]) -> (f32, f32, f32, f32) {
+    let [r, g, b] = rgb;
+    let max: f32 = r.max(g).max(b);
+    let min: f32 = r.min(g).min(b);
+    let c: f32 = max - min;
+    let h: f32 = if c == 0.0 {
+        0.0
+    } else if max == r {
+        60.0 * ((g - b) / c).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / c + 2.0)
+    } else {
+        60.0 * ((r - g) / c + 4.0)
+    };
+    (h, c, max, min)
+}
+
+/// RGB channels of hue `h` in degrees with chroma `c`, offset by `m`.
+fn from_hue_chroma(h: f32, c: f32, m: f32) -> [f32; 3] {
+    let h: f32 = h.rem_euclid(360.0) / 60.0;
+    let x: f32 = c * (1.0 - (h.rem_euclid(2.0) - 1.0).abs());
+    let [r, g, b] = match h as u32 {
+        0 => [c, x, 0.0],
+        1 => [x, c, 0.0],
+        2 => [0.0, c, x],
+        3 => [0.0, x, c],
+        4 => [x, 0.0, c],
+        _ => [c, 0.0, x],
+    };
+    [r + m, g + m, b + m]
+}
+
+/// Hue in degrees, saturation and lightness of RGB channels in
+/// `0.0..=1.0`.
+pub fn rgb_to_hsl(rgb: [f32; 3]) -> [f32; 3] {
+    let (h, c, max, min) = hue_chroma(rgb);
+    let l: f32 = (max + min) / 2.0;
+    let s: f32 = if c == 0.0 {
+        0.0
+    } else {
+        c / (1.0 - (2.0 * l - 1.0).abs())
+    };
+    [h, s, l]
+}
+
+/// RGB channels in `0.0..=1.0` of hue `h` in degrees, saturation `s` and
+/// lightness `l`.
+pub fn hsl_to_rgb([h, s, l]: [f32; 3]) -> [f32; 3] {
+    let c: f32 = (1.0 - (2.0 * l - 1.0).abs()) * s;
+    from_hue_chroma(h, c, l - c / 2.0)
+}
+
+/// Hue in degrees, saturation and value of RGB channels in `0.0..=1.0`.
+pub fn rgb_to_hsv(rgb: [f32; 3]) -> [f32; 3] {
+    let (h, c, max, _) = hue_chroma(rgb);
+    let s: f32 = if max == 0.0 { 0.0 } else { c / max };
+    [h, s, max]
+}
+
+/// RGB channels in `0.0..=1.0` of hue `h` in degrees, saturation `s` and
+/// value `v`.
+pub fn hsv_to_rgb([h, s, v]: [f32; 3]) -> [f32; 3] {
+    let c: f32 = v * s;
+    from_hue_chroma(h, c, v - c)
+}
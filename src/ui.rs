@@ -1,57 +1,71 @@
 use druid::{
-    commands,
-    lens::LensExt,
+    commands, lens,
     piet::ImageFormat,
     widget::{
-        Align, Button, Container, Controller, CrossAxisAlignment, Flex, Image, Label,
-        MainAxisAlignment, RadioGroup, Slider, TextBox, ViewSwitcher,
+        Align, Button, Checkbox, Container, Controller, CrossAxisAlignment, Flex, Image, Label,
+        MainAxisAlignment, RadioGroup, Scroll, Slider, TextBox, ViewSwitcher,
     },
-    Color, Data, FileDialogOptions, FileSpec, ImageBuf, Lens, UnitPoint, Widget, WidgetExt,
+    Color, Data, FileDialogOptions, FileSpec, ImageBuf, UnitPoint, Widget, WidgetExt,
 };
-use image::{ImageBuffer, ImageReader, Rgb};
-use std::{
-    path::Path,
-    sync::{Arc, Mutex},
+use rust_image_processing::{
+    filter::{Filter, ParamKind, ParamSpec, Params, FILTERS},
+    image::DynamicImage,
+    open_img_dynamic,
+    palettes::PALETTES,
+    Error,
 };
+use std::sync::{Arc, Mutex};
 
-use crate::{
-    image_procs::{ord_bayer_dithering, str_to_pal, BAYER_8X8},
-    AppState,
-};
+use crate::{AppState, SharedImage};
 
 static MAX_UNDOS_LEN: u8 = 3;
 
-#[derive(Clone, Data, PartialEq)]
-pub enum ProcessingOption {
-    Dithering,
-    Color,
-}
-
-#[derive(Clone, Data, Lens)]
-pub struct DitheringParams {
-    pub palette: String,
-    pub pixel_size: f64,
-}
+/// Sliders are only offered for ranges up to this wide; wider or unbounded
+/// numbers are typed in.
+const MAX_SLIDER_RANGE: f64 = 1000.0;
 
-#[derive(Clone, Data, Lens)]
-pub struct ColorParams {
-    pub brightness: f64,
-    pub contrast: f64,
-}
+/// Values of the parameters of a [`Filter`], in the order of its `params()`
+/// and in command line form. Parameters left unset are empty.
+#[derive(Clone, Data, PartialEq)]
+pub struct ParamValues(#[data(eq)] pub Vec<String>);
 
-struct FileOpenController;
-struct DitheringOrderedController;
+impl ParamValues {
+    /// The defaults of `filter`, empty where it has none.
+    pub fn defaults(filter: &dyn Filter) -> ParamValues {
+        ParamValues(
+            filter
+                .params()
+                .iter()
+                .map(|spec| spec.default.unwrap_or_default().to_string())
+                .collect(),
+        )
+    }
 
-impl PartialEq for DitheringParams {
-    fn eq(&self, other: &Self) -> bool {
-        (self.palette == other.palette) && (self.pixel_size == other.pixel_size)
+    /// Whether running `filter` with these values writes to a file.
+    fn write_files(&self, filter: &dyn Filter) -> bool {
+        filter
+            .params()
+            .iter()
+            .zip(&self.0)
+            .any(|(spec, val)| spec.kind == ParamKind::File && !val.is_empty())
     }
-    fn ne(&self, other: &Self) -> bool {
-        (self.palette != other.palette) && (self.pixel_size != other.pixel_size)
+
+    /// Runs `filter` on `img` with the values that are set.
+    fn run(&self, filter: &dyn Filter, img: &DynamicImage) -> Result<DynamicImage, Error> {
+        let values = filter
+            .params()
+            .iter()
+            .zip(&self.0)
+            .filter(|(_, val)| !val.is_empty())
+            .map(|(spec, val)| (spec.name.to_string(), val.clone()));
+        filter.apply(img.clone(), &Params::new(filter, values)?)
     }
 }
 
-impl<W: druid::Widget<AppState>> Controller<AppState, W> for FileOpenController {
+struct FileOpenController;
+struct FilterController;
+
+impl<W: Widget<AppState>> Controller<AppState, W> for FileOpenController {
     fn event(
         &mut self,
         child: &mut W,
@@ -62,12 +76,19 @@ impl<W: druid::Widget<AppState>> Controller<AppState, W> for FileOpenController
     ) {
         if let druid::Event::Command(cmd) = event {
             if let Some(file_info) = cmd.get(commands::OPEN_FILE) {
-                if let Some(img_buf) = image_from_path(file_info.path()) {
-                    if let Some(curr_img) = &data.img {
-                        update_undos(&mut data.undos, curr_img;
+                match open_img_dynamic(file_info.path()) {
+                    Ok(img_buf) => {
+                        if let Some(base) = &data.base {
+                            update_undos(&data.undos, base.clone());
+                        }
+                        let img = Arc::new(Mutex::new(img_buf));
+                        data.base = Some(img.clone());
+                        data.img = Some(img);
+                        ctx.request_update();
+                    }
+                    Err(err) => {
+                        eprintln!("cannot open `{}`: {}", file_info.path().display(), err)
                     }
-                    data.img = Some(img_buf.clone());
-                    ctx.request_update();
                 }
                 ctx.set_handled();
                 return;
@@ -77,33 +98,56 @@ impl<W: druid::Widget<AppState>> Controller<AppState, W> for FileOpenController
     }
 }
 
-impl<W: Widget<AppState>> Controller<AppState, W> for DitheringOrderedController {
-    fn update(
+impl<W: Widget<AppState>> Controller<AppState, W> for FilterController {
+    fn event(
         &mut self,
         child: &mut W,
-        ctx: &mut druid::UpdateCtx,
-        old_data: &AppState,
-        data: &AppState,
+        ctx: &mut druid::EventCtx,
+        event: &druid::Event,
+        data: &mut AppState,
         env: &druid::Env,
     ) {
-        if old_data.dithering_params != data.dithering_params && data.img.is_some() {
-            let params = data.dithering_params;
-            let img = data.img.unwrap().lock().unwrap();
-            if let Some(pal) = str_to_pal(params.palette.as_str()) {
-                data.img = Some(Arc::new(Mutex::new(ord_bayer_dithering(
-                    img.clone(),
-                    pal,
-                    BAYER_8X8,
-                    params.pixel_size as u32,
-                    1.0,
-                    1.0,
-                ))));
+        let (old_filter, old_values) = (data.filter, data.values.clone());
+        child.event(ctx, event, data, env);
+        if data.filter != old_filter {
+            data.values = ParamValues::defaults(FILTERS[data.filter]);
+        }
+        if data.filter != old_filter || !old_values.same(&data.values) {
+            preview(data, false);
+        }
+    }
+}
+
+/// Shows the selected filter run on the image with all applied steps, or
+/// why it cannot run. With `apply` the result becomes the image further
+/// steps build on, and the image before it can be restored with Undo.
+/// Filters that write files only run when `apply` is set, so they do not
+/// write a file for every change of a parameter.
+fn preview(data: &mut AppState, apply: bool) {
+    let Some(base) = data.base.clone() else {
+        return;
+    };
+    let filter: &dyn Filter = FILTERS[data.filter];
+    if !apply && data.values.write_files(filter) {
+        data.error = "Press Apply to write the output files".to_string();
+        return;
+    }
+    let img = data.values.run(filter, &base.lock().unwrap());
+    match img {
+        Ok(img) => {
+            let img = Arc::new(Mutex::new(img));
+            if apply {
+                update_undos(&data.undos, base);
+                data.base = Some(img.clone());
             }
+            data.img = Some(img);
+            data.error.clear();
         }
-        child.update(ctx, old_data, data, env);
+        Err(err) => data.error = err.to_string(),
     }
 }
-fn update_undos(undos: &mut Arc<Mutex<Vec<ImageBuf>>>, img: ImageBuf) {
+
+fn update_undos(undos: &Mutex<Vec<SharedImage>>, img: SharedImage) {
     let mut images_lock = undos.lock().unwrap();
     images_lock.push(img);
     if images_lock.len() > MAX_UNDOS_LEN as usize {
@@ -111,10 +155,6 @@ fn update_undos(undos: &mut Arc<Mutex<Vec<ImageBuf>>>, img: ImageBuf) {
     }
 }
 
-fn image_from_path<P: AsRef<Path>>(path: P) -> Option<ImageBuffer<Rgb<u8>, Vec<u8>>> {
-    Some(ImageReader::open(path).ok()?.decode().ok()?.into_rgb8())
-}
-
 pub fn build_ui() -> impl Widget<AppState> {
     let file_button = Button::new("Open Image").on_click(|ctx, _data: &mut AppState, _env| {
         let options = FileDialogOptions::new()
@@ -126,31 +166,32 @@ pub fn build_ui() -> impl Widget<AppState> {
         ctx.submit_command(commands::SHOW_OPEN_PANEL.with(options));
     });
 
-    let processing_dropdown = RadioGroup::column(vec![
-        ("Dithering".to_string(), ProcessingOption::Dithering),
-        ("Color".to_string(), ProcessingOption::Color),
-    ])
-    .lens(AppState::selected_option);
+    let filter_list = RadioGroup::column(
+        FILTERS
+            .iter()
+            .enumerate()
+            .map(|(i, filter)| (filter.name(), i)),
+    )
+    .lens(AppState::filter);
 
     let params_view = ViewSwitcher::new(
-        |data: &AppState, _env| data.selected_option.clone(),
-        |selected, _data, _env| match selected {
-            ProcessingOption::Dithering => dithering_ordered_params_ui()
-                .lens(AppState::dithering_params)
-                .boxed(),
-            ProcessingOption::Color => color_params_ui().lens(AppState::color_params).boxed(),
-        },
+        |data: &AppState, _env| data.filter,
+        |&filter, _data, _env| params_ui(FILTERS[filter]).lens(AppState::values).boxed(),
     );
 
+    let apply_button =
+        Button::new("Apply").on_click(|_ctx, data: &mut AppState, _env| preview(data, true));
+
     let image_view = ViewSwitcher::new(
         |data: &AppState, _env| data.img.clone(),
         |image_opt, _data, _env| {
             if let Some(img) = image_opt {
+                let img = img.lock().unwrap().to_rgba8();
                 let (width, height) = img.dimensions();
-                let raw_bytes = img.into_raw();
+                // Straight (not premultiplied) alpha, as `image` stores it.
                 Image::new(ImageBuf::from_raw(
-                    raw_bytes,
-                    ImageFormat::Rgb,
+                    img.as_raw().as_slice(),
+                    ImageFormat::RgbaSeparate,
                     width as usize,
                     height as usize,
                 ))
@@ -165,6 +206,7 @@ pub fn build_ui() -> impl Widget<AppState> {
     let undo_button = Button::new("Undo").on_click(|_ctx, data: &mut AppState, _env| {
         let mut images_lock = data.undos.lock().unwrap();
         if let Some(prev_img) = images_lock.pop() {
+            data.base = Some(prev_img.clone());
             data.img = Some(prev_img);
         }
     });
@@ -172,71 +214,103 @@ pub fn build_ui() -> impl Widget<AppState> {
     let right_col = Flex::column()
         .with_flex_child(Align::new(UnitPoint::CENTER, image_view), 1.0)
         .with_spacer(10.0)
-        .with_flex_child(undo_button, 1.0);
+        .with_child(Label::dynamic(|data: &AppState, _env| data.error.clone()))
+        .with_spacer(10.0)
+        .with_child(undo_button);
 
     let left_col = Flex::column()
         .with_child(file_button)
         .with_spacer(10.0)
-        .with_child(Label::new("Select Processing Option:"))
-        .with_child(processing_dropdown)
+        .with_child(Label::new("Select Filter:"))
+        .with_flex_child(Scroll::new(filter_list).vertical(), 1.0);
+
+    let middle_col = Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_flex_child(Scroll::new(params_view).vertical(), 1.0)
         .with_spacer(10.0)
-        .with_child(params_view);
+        .with_child(apply_button);
 
     Container::new(
         Flex::row()
             .cross_axis_alignment(CrossAxisAlignment::Center)
             .main_axis_alignment(MainAxisAlignment::Center)
             .with_flex_child(left_col, 1.0)
-            .with_flex_child(right_col, 1.0)
+            .with_flex_child(middle_col, 1.0)
+            .with_flex_child(right_col, 2.0)
             .controller(FileOpenController)
-            .controller(DitheringOrderedController),
+            .controller(FilterController),
     )
     .background(Color::BLACK)
-    .padding((30., 200.))
+    .padding((30., 30.))
 }
 
-fn dithering_ordered_params_ui() -> impl Widget<DitheringParams> {
-    Flex::column()
-        .with_child(Label::new("Dithering Parameters"))
-        .with_spacer(5.0)
-        .with_child(Label::new("Palette").padding((0., 0., 0., 5.)))
-        .with_child(
-            TextBox::new()
-                .with_placeholder("Enter your name")
-                .lens(DitheringParams::palette)
-                .padding((0., 0., 0., 5.)),
-        )
-        .with_child(Label::new("Pixel size").padding((0., 0., 0., 5.)))
-        .with_child(
-            Slider::new()
-                .with_range(0.0, 20.0)
-                .with_step(1.0)
-                .lens(DitheringParams::pixel_size),
-        )
-        .with_child(Label::new("").padding((0., 0., 0., 5.)))
-        .with_child(
-            Slider::new()
-                .with_range(0.0, 20.0)
-                .with_step(1.0)
-                .lens(DitheringParams::pixel_size),
-        )
+/// A label and a control for every parameter of `filter`.
+fn params_ui(filter: &dyn Filter) -> impl Widget<ParamValues> {
+    let mut column = Flex::column().cross_axis_alignment(CrossAxisAlignment::Start);
+    column.add_child(Label::new(format!("{} parameters", filter.name())));
+    for (i, spec) in filter.params().iter().enumerate() {
+        let value = lens::Map::new(
+            move |values: &ParamValues| values.0[i].clone(),
+            move |values: &mut ParamValues, val: String| values.0[i] = val,
+        );
+        column.add_spacer(5.0);
+        column.add_child(Label::new(spec.name).padding((0., 0., 0., 5.)));
+        column.add_child(param_ui(spec).lens(value));
+    }
+    column
 }
 
-fn color_params_ui() -> impl Widget<ColorParams> {
-    Flex::column()
-        .with_child(Label::new("Color Parameters"))
-        .with_spacer(5.0)
-        .with_child(Label::new("Brightness:").padding((0., 0., 0., 5.)))
-        .with_child(
-            Slider::new()
-                .with_range(0.0, 2.0)
-                .lens(ColorParams::brightness),
-        )
-        .with_spacer(5.0)
-        .with_child(Label::new("Contrast:").padding((0., 0., 0., 5.)))
-        .with_child(
-            Slider::new()
-                .with_range(0.0, 2.0)
-                .lens(ColorParams::contrast),
-        )
+/// The control for a parameter of kind `spec.kind`: a checkbox, a slider
+/// for numbers of a narrow range, a choice of words or of the built-in
+/// palettes, or a text box in the form the command line takes.
+fn param_ui(spec: &ParamSpec) -> Box<dyn Widget<String>> {
+    let slider = |min: f64, max: f64, step: Option<f64>| {
+        let slider = Slider::new().with_range(min, max);
+        let slider = match step {
+            Some(step) => slider.with_step(step),
+            None => slider,
+        };
+        let number = lens::Map::new(
+            move |val: &String| val.parse().unwrap_or(min),
+            move |val: &mut String, v: f64| {
+                *val = match step {
+                    Some(_) => format!("{}", v.round() as i64),
+                    None => format!("{:.2}", v),
+                }
+            },
+        );
+        Flex::row()
+            .with_child(slider.lens(number))
+            .with_spacer(5.0)
+            .with_child(Label::dynamic(|val: &String, _env| val.clone()))
+            .boxed()
+    };
+    match spec.kind {
+        ParamKind::Bool => Checkbox::new("")
+            .lens(lens::Map::new(
+                |val: &String| val == "true",
+                |val: &mut String, on: bool| *val = on.to_string(),
+            ))
+            .boxed(),
+        ParamKind::Int { min, max } if (max - min) as f64 <= MAX_SLIDER_RANGE => {
+            slider(min as f64, max as f64, Some(1.0))
+        }
+        ParamKind::Float { min, max } if max - min <= MAX_SLIDER_RANGE => slider(min, max, None),
+        ParamKind::Choice(choices) => {
+            RadioGroup::column(choices.iter().map(|&choice| (choice, choice.to_string()))).boxed()
+        }
+        ParamKind::Palette => Flex::row()
+            .cross_axis_alignment(CrossAxisAlignment::Start)
+            .with_child(TextBox::new().with_placeholder("palette"))
+            .with_spacer(5.0)
+            .with_child(RadioGroup::column(
+                PALETTES
+                    .iter()
+                    .map(|palette| (palette.name, palette.name.to_string())),
+            ))
+            .boxed(),
+        kind => TextBox::new()
+            .with_placeholder(kind.placeholder())
+            .boxed(),
+    }
 }